@@ -0,0 +1,310 @@
+//! Reporting which glyphs a conversion would add, remove or modify, for dry
+//! runs.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::error::Result;
+use crate::output::{json_string, OutputFormat};
+use crate::to_designspace::convert_glyphs_glyph_to_ufo_glyph;
+use crate::Ufos;
+
+/// A part of a glyph that changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Aspect {
+    Outline,
+    Width,
+    Anchors,
+    Components,
+    Unicodes,
+}
+
+impl Aspect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aspect::Outline => "outline",
+            Aspect::Width => "width",
+            Aspect::Anchors => "anchors",
+            Aspect::Components => "components",
+            Aspect::Unicodes => "unicodes",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified(Vec<Aspect>),
+}
+
+/// A glyph that would change in a layer of a UFO or Glyphs.app file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlyphChange {
+    /// The UFO file name, or the Glyphs.app file name.
+    pub source: String,
+    pub layer: String,
+    pub glyph: String,
+    pub change: Change,
+}
+
+impl fmt::Display for GlyphChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, layer '{}': glyph '{}' ",
+            self.source, self.layer, self.glyph
+        )?;
+        match &self.change {
+            Change::Added => write!(f, "added"),
+            Change::Removed => write!(f, "removed"),
+            Change::Modified(aspects) => {
+                let aspects: Vec<&str> = aspects.iter().map(Aspect::as_str).collect();
+                write!(f, "modified ({})", aspects.join(", "))
+            }
+        }
+    }
+}
+
+/// Glyphs by name, in layers by name.
+type Layers<'a> = BTreeMap<String, BTreeMap<String, Cow<'a, norad::Glyph>>>;
+
+/// Compare the UFOs before and after a conversion, by source file name.
+pub fn compare_ufos(old: &Ufos, new: &Ufos) -> Vec<GlyphChange> {
+    let filenames: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    filenames
+        .into_iter()
+        .flat_map(|filename| {
+            let old = old.get(filename).map(ufo_layers).unwrap_or_default();
+            let new = new.get(filename).map(ufo_layers).unwrap_or_default();
+            compare_layers(filename, &old, &new)
+        })
+        .collect()
+}
+
+/// Compare Glyphs.app files before and after a conversion. Master layers
+/// are matched by master name, as master IDs are made up anew each time.
+pub fn compare_glyphs_fonts(
+    source: &str,
+    old: Option<&glyphs_plist::Font>,
+    new: &glyphs_plist::Font,
+) -> Result<Vec<GlyphChange>> {
+    let old = old.map(glyphs_layers).transpose()?.unwrap_or_default();
+    Ok(compare_layers(source, &old, &glyphs_layers(new)?))
+}
+
+fn ufo_layers(ufo: &norad::Font) -> Layers<'_> {
+    ufo.layers
+        .iter()
+        .map(|layer| {
+            let glyphs = layer
+                .iter()
+                .map(|glyph| (glyph.name().to_string(), Cow::Borrowed(glyph)))
+                .collect();
+            (layer.name().to_string(), glyphs)
+        })
+        .collect()
+}
+
+fn glyphs_layers(font: &glyphs_plist::Font) -> Result<Layers<'static>> {
+    let master_name = |id: &str| {
+        font.font_master
+            .iter()
+            .find(|master| master.id == id)
+            .and_then(|master| master.name())
+            .unwrap_or(id)
+            .to_string()
+    };
+    let mut layers = Layers::new();
+    for glyph in &font.glyphs {
+        for layer in &glyph.layers {
+            let name = match (&layer.associated_master_id, &layer.name) {
+                (Some(master_id), Some(name)) => format!("{} ({})", name, master_name(master_id)),
+                _ => master_name(&layer.layer_id),
+            };
+            layers.entry(name).or_default().insert(
+                glyph.glyphname.to_string(),
+                Cow::Owned(convert_glyphs_glyph_to_ufo_glyph(glyph, layer)?),
+            );
+        }
+    }
+    Ok(layers)
+}
+
+fn compare_layers(source: &str, old: &Layers<'_>, new: &Layers<'_>) -> Vec<GlyphChange> {
+    let empty = BTreeMap::new();
+    let layer_names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut changes = Vec::new();
+    for layer in layer_names {
+        let old = old.get(layer).unwrap_or(&empty);
+        let new = new.get(layer).unwrap_or(&empty);
+        let glyph_names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for glyph in glyph_names {
+            let change = match (old.get(glyph), new.get(glyph)) {
+                (None, Some(_)) => Change::Added,
+                (Some(_), None) => Change::Removed,
+                (Some(old), Some(new)) => {
+                    let aspects = changed_aspects(old, new);
+                    if aspects.is_empty() {
+                        continue;
+                    }
+                    Change::Modified(aspects)
+                }
+                (None, None) => continue,
+            };
+            changes.push(GlyphChange {
+                source: source.to_string(),
+                layer: layer.clone(),
+                glyph: glyph.clone(),
+                change,
+            });
+        }
+    }
+    changes
+}
+
+/// What differs between two glyphs, ignoring identifiers and libs.
+fn changed_aspects(old: &norad::Glyph, new: &norad::Glyph) -> Vec<Aspect> {
+    let outline = |glyph: &norad::Glyph| -> Vec<Vec<(f64, f64, norad::PointType, bool)>> {
+        glyph
+            .contours
+            .iter()
+            .map(|contour| {
+                contour
+                    .points
+                    .iter()
+                    .map(|p| (p.x, p.y, p.typ.clone(), p.smooth))
+                    .collect()
+            })
+            .collect()
+    };
+    let anchors = |glyph: &norad::Glyph| -> Vec<(Option<String>, f64, f64)> {
+        glyph
+            .anchors
+            .iter()
+            .map(|a| (a.name.as_ref().map(|n| n.to_string()), a.x, a.y))
+            .collect()
+    };
+    let components = |glyph: &norad::Glyph| -> Vec<(String, norad::AffineTransform)> {
+        glyph
+            .components
+            .iter()
+            .map(|c| (c.base.to_string(), c.transform))
+            .collect()
+    };
+
+    let mut aspects = Vec::new();
+    if outline(old) != outline(new) {
+        aspects.push(Aspect::Outline);
+    }
+    if old.width != new.width {
+        aspects.push(Aspect::Width);
+    }
+    if anchors(old) != anchors(new) {
+        aspects.push(Aspect::Anchors);
+    }
+    if components(old) != components(new) {
+        aspects.push(Aspect::Components);
+    }
+    if old.codepoints != new.codepoints {
+        aspects.push(Aspect::Unicodes);
+    }
+    aspects
+}
+
+/// Print the changes of a dry run.
+pub fn print_changes(changes: &[GlyphChange], format: OutputFormat) {
+    match format {
+        OutputFormat::Human => {
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+        OutputFormat::Json => println!("{}", changes_to_json(changes)),
+    }
+}
+
+fn changes_to_json(changes: &[GlyphChange]) -> String {
+    let objects: Vec<String> = changes
+        .iter()
+        .map(|change| {
+            let (kind, aspects) = match &change.change {
+                Change::Added => ("added", Vec::new()),
+                Change::Removed => ("removed", Vec::new()),
+                Change::Modified(aspects) => (
+                    "modified",
+                    aspects.iter().map(|a| json_string(a.as_str())).collect(),
+                ),
+            };
+            format!(
+                r#"{{"source": {}, "layer": {}, "glyph": {}, "change": {}, "aspects": [{}]}}"#,
+                json_string(&change.source),
+                json_string(&change.layer),
+                json_string(&change.glyph),
+                json_string(kind),
+                aspects.join(", ")
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    const SQUARE: &[(f64, f64)] = &[(100.0, 0.0), (400.0, 0.0), (400.0, 300.0), (100.0, 300.0)];
+    const TRIANGLE: &[(f64, f64)] = &[(0.0, 0.0), (500.0, 0.0), (250.0, 400.0)];
+
+    #[test]
+    fn report_changes() {
+        let filename = fixtures::ufo_filename("Regular");
+        let old: Ufos = [(
+            filename.clone(),
+            fixtures::ufo(
+                "Regular",
+                vec![
+                    fixtures::ufo_glyph("a", 500.0, SQUARE),
+                    fixtures::ufo_glyph("b", 500.0, SQUARE),
+                    fixtures::ufo_glyph("d", 500.0, SQUARE),
+                ],
+            ),
+        )]
+        .into();
+        let new: Ufos = [(
+            filename,
+            fixtures::ufo(
+                "Regular",
+                vec![
+                    fixtures::ufo_glyph("a", 600.0, TRIANGLE),
+                    fixtures::ufo_glyph("c", 500.0, SQUARE),
+                    fixtures::ufo_glyph("d", 500.0, SQUARE),
+                ],
+            ),
+        )]
+        .into();
+
+        let changes = compare_ufos(&old, &new);
+        let report: Vec<String> = changes.iter().map(|change| change.to_string()).collect();
+        assert_eq!(
+            report,
+            vec![
+                "Test-Regular.ufo, layer 'public.default': glyph 'a' modified (outline, width)",
+                "Test-Regular.ufo, layer 'public.default': glyph 'b' removed",
+                "Test-Regular.ufo, layer 'public.default': glyph 'c' added",
+            ]
+        );
+        assert_eq!(
+            changes_to_json(&changes[..2]),
+            concat!(
+                r#"[{"source": "Test-Regular.ufo", "layer": "public.default", "glyph": "a", "#,
+                r#""change": "modified", "aspects": ["outline", "width"]}, "#,
+                r#"{"source": "Test-Regular.ufo", "layer": "public.default", "glyph": "b", "#,
+                r#""change": "removed", "aspects": []}]"#,
+            )
+        );
+        assert!(compare_ufos(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,15 @@
+use std::path::Path;
+
+use crate::error::{load_glyphs_font, Result};
+
+/// Check that all master and brace layers of the glyphs in a Glyphs.app file
+/// can be interpolated, printing every incompatibility. Returns whether the
+/// file is compatible.
+pub fn command_check(glyphs_path: &Path) -> Result<bool> {
+    let font = load_glyphs_font(glyphs_path)?;
+    let incompatibilities = font.check_compatibility();
+    for incompatibility in &incompatibilities {
+        println!("{}", incompatibility);
+    }
+    Ok(incompatibilities.is_empty())
+}
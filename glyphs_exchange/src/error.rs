@@ -0,0 +1,195 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glyphs_plist::{FromPlist, Plist};
+
+/// Why a command failed. Each kind maps to its own exit code, so scripts can
+/// tell broken input from a bug, which panics and exits with 101.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A Glyphs.app file or UFO could not be parsed.
+    Parse { path: PathBuf, message: String },
+    /// A Glyphs.app file or UFO could not be saved.
+    Save { path: PathBuf, message: String },
+    /// A Designspace could not be loaded or saved, or is missing data.
+    Designspace { path: PathBuf, message: String },
+    /// A location or value could not be mapped between the formats.
+    Mapping(String),
+    /// The input failed the checks run before converting.
+    Validation(Vec<String>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Exit codes, besides 1 for checks that found problems, 2 for invalid
+/// command lines and 101 for bugs.
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io { .. } => 3,
+            Error::Parse { .. } => 4,
+            Error::Designspace { .. } => 5,
+            Error::Mapping(_) => 6,
+            Error::Validation(_) => 7,
+            Error::Save { .. } => 8,
+        }
+    }
+
+    pub(crate) fn io(path: &Path, source: std::io::Error) -> Self {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn parse(path: &Path, message: impl fmt::Debug) -> Self {
+        Error::Parse {
+            path: path.to_path_buf(),
+            message: format!("{:?}", message),
+        }
+    }
+
+    pub(crate) fn save(path: &Path, message: impl fmt::Debug) -> Self {
+        Error::Save {
+            path: path.to_path_buf(),
+            message: format!("{:?}", message),
+        }
+    }
+
+    pub(crate) fn designspace(path: &Path, message: impl Into<String>) -> Self {
+        Error::Designspace {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { path, message } => {
+                write!(f, "cannot parse {}: {}", path.display(), message)
+            }
+            Error::Save { path, message } => {
+                write!(f, "cannot save {}: {}", path.display(), message)
+            }
+            Error::Designspace { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Mapping(message) => write!(f, "{}", message),
+            Error::Validation(problems) => match problems.as_slice() {
+                [problem] => write!(f, "{}", problem),
+                _ => write!(f, "{} problems found", problems.len()),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The directory of the file at `path`, which other files are relative to.
+pub(crate) fn parent_dir(path: &Path) -> Result<&Path> {
+    path.parent().ok_or_else(|| {
+        Error::Validation(vec![format!("{} is not a path to a file.", path.display())])
+    })
+}
+
+/// Read and parse a Glyphs.app file without converting it.
+pub(crate) fn read_glyphs_plist(path: &Path) -> Result<Plist> {
+    let contents = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    Plist::parse(&contents).map_err(|e| Error::parse(path, e))
+}
+
+/// Load a Glyphs.app file, telling read from parse errors apart, unlike
+/// [`glyphs_plist::Font::load`].
+pub fn load_glyphs_font(path: &Path) -> Result<glyphs_plist::Font> {
+    read_glyphs_plist(path).and_then(|plist| glyphs_font_from_plist(path, plist))
+}
+
+/// Convert the plist of the Glyphs.app file at `path`.
+pub(crate) fn glyphs_font_from_plist(path: &Path, plist: Plist) -> Result<glyphs_plist::Font> {
+    FromPlist::from_plist(plist).map_err(|message| Error::Parse {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+/// Save a Glyphs.app file.
+pub fn save_glyphs_font(font: glyphs_plist::Font, path: &Path) -> Result<()> {
+    font.save(path).map_err(|e| Error::save(path, e))
+}
+
+/// Load a UFO. norad reports missing files as parse errors, so check first.
+pub(crate) fn load_ufo(path: &Path) -> Result<norad::Font> {
+    if !path.exists() {
+        return Err(Error::io(
+            path,
+            std::io::Error::new(std::io::ErrorKind::NotFound, "UFO does not exist"),
+        ));
+    }
+    norad::Font::load(path).map_err(|e| Error::parse(path, e))
+}
+
+/// Save a UFO.
+pub(crate) fn save_ufo(ufo: &norad::Font, path: &Path) -> Result<()> {
+    ufo.save(path).map_err(|e| Error::save(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let path = Path::new("Test.glyphs");
+        let errors = [
+            Error::io(path, std::io::ErrorKind::NotFound.into()),
+            Error::parse(path, "unexpected end"),
+            Error::save(path, "disk full"),
+            Error::designspace(path, "no axes"),
+            Error::Mapping("no mapping".into()),
+            Error::Validation(vec!["first".into(), "second".into()]),
+        ];
+        let codes: Vec<i32> = errors.iter().map(Error::exit_code).collect();
+        assert_eq!(codes, vec![3, 4, 8, 5, 6, 7]);
+
+        assert_eq!(
+            errors[2].to_string(),
+            "cannot save Test.glyphs: \"disk full\""
+        );
+        assert_eq!(errors[5].to_string(), "2 problems found");
+        assert!(std::error::Error::source(&errors[0]).is_some());
+    }
+
+    #[test]
+    fn bad_glyphs_structure() {
+        let path = Path::new("Test.glyphs");
+        let plist = Plist::parse(
+            "{familyName = Test; versionMajor = 1; versionMinor = 0; unitsPerEm = 1000; \
+             fontMaster = ({id = m01;}); glyphs = ({glyphname = a; layers = ({layerId = m01; \
+             width = 500; paths = ({closed = 1; nodes = ((0,0,x));});});});}",
+        )
+        .unwrap();
+        match glyphs_font_from_plist(path, plist) {
+            Err(Error::Parse { message, .. }) => {
+                assert_eq!(message, "glyphs: layers: paths: nodes: unknown node type x")
+            }
+            other => panic!("expected a parse error, got {:?}", other.map(drop)),
+        }
+        assert!(matches!(
+            parent_dir(Path::new("/")),
+            Err(Error::Validation(_))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! Designspaces, UFOs and glyphs for tests, built in memory.
+
+use glyphs_plist::{FromPlist, Plist};
+use norad::designspace::{Axis, DesignSpaceDocument, Dimension, Source};
+
+use crate::to_designspace::convert_glyphs_glyph_to_ufo_glyph;
+use crate::Ufos;
+
+/// A Glyphs.app glyph with one master layer "m01" and a closed path through
+/// `nodes`.
+pub(crate) fn glyphs_glyph(name: &str, width: f64, nodes: &[(f64, f64)]) -> glyphs_plist::Glyph {
+    let nodes: Vec<String> = nodes
+        .iter()
+        .map(|(x, y)| format!("\"{} {} LINE\"", x, y))
+        .collect();
+    let source = format!(
+        "{{glyphname = {}; layers = ({{layerId = m01; width = {}; paths = ({{closed = 1; nodes = ({});}});}});}}",
+        name,
+        width,
+        nodes.join(", ")
+    );
+    FromPlist::from_plist(Plist::parse(&source).unwrap()).unwrap()
+}
+
+/// A UFO glyph with a closed contour through `nodes`.
+pub(crate) fn ufo_glyph(name: &str, width: f64, nodes: &[(f64, f64)]) -> norad::Glyph {
+    let glyph = glyphs_glyph(name, width, nodes);
+    convert_glyphs_glyph_to_ufo_glyph(&glyph, &glyph.layers[0]).unwrap()
+}
+
+/// The points of the contours of a UFO glyph.
+pub(crate) fn points(glyph: &norad::Glyph) -> Vec<Vec<(f64, f64)>> {
+    glyph
+        .contours
+        .iter()
+        .map(|contour| contour.points.iter().map(|p| (p.x, p.y)).collect())
+        .collect()
+}
+
+/// A master UFO of the family "Test" with `glyphs` in its default layer.
+pub(crate) fn ufo(style_name: &str, glyphs: Vec<norad::Glyph>) -> norad::Font {
+    let mut ufo = norad::Font::new();
+    ufo.font_info.family_name = Some("Test".into());
+    ufo.font_info.style_name = Some(style_name.into());
+    ufo.font_info.units_per_em = norad::fontinfo::NonNegativeIntegerOrFloat::new(1000.0);
+    for glyph in glyphs {
+        ufo.default_layer_mut().insert_glyph(glyph);
+    }
+    ufo
+}
+
+/// The UFO file name of a source.
+pub(crate) fn ufo_filename(style_name: &str) -> String {
+    format!("Test-{}.ufo", style_name)
+}
+
+/// A Designspace source at `weight`, in the UFO of [`ufo_filename`].
+pub(crate) fn source(style_name: &str, weight: f32) -> Source {
+    Source {
+        familyname: Some("Test".into()),
+        stylename: Some(style_name.into()),
+        name: format!("Test {}", style_name),
+        filename: ufo_filename(style_name),
+        location: vec![Dimension {
+            name: "Weight".into(),
+            xvalue: Some(weight),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// A Designspace with a weight axis from 400 to 700, defaulting to 400.
+pub(crate) fn designspace(sources: Vec<Source>) -> DesignSpaceDocument {
+    DesignSpaceDocument {
+        axes: vec![Axis {
+            name: "Weight".into(),
+            tag: "wght".into(),
+            default: 400.0,
+            minimum: Some(400.0),
+            maximum: Some(700.0),
+            ..Default::default()
+        }],
+        sources,
+        ..Default::default()
+    }
+}
+
+/// A Designspace with a master at weight 400 and 700, and their UFOs with
+/// `glyphs`.
+pub(crate) fn masters(glyphs: Vec<norad::Glyph>) -> (DesignSpaceDocument, Ufos) {
+    let designspace = designspace(vec![source("Regular", 400.0), source("Bold", 700.0)]);
+    let ufos = ["Regular", "Bold"]
+        .into_iter()
+        .map(|style| (ufo_filename(style), ufo(style, glyphs.clone())))
+        .collect();
+    (designspace, ufos)
+}
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use glyphs_plist::Plist;
+use log::warn;
+use rayon::prelude::*;
+
+use crate::error::{load_glyphs_font, save_ufo, Result};
+use crate::to_designspace::convert_glyphs_glyph_to_ufo_glyph;
+
+/// Options for interpolating the instances of a Glyphs.app file.
+#[derive(Debug, Default)]
+pub struct InstancesOptions {
+    /// Names of the instances to interpolate. All exported instances if
+    /// empty.
+    pub instance_names: HashSet<String>,
+}
+
+pub fn command_instances(
+    glyphs_path: &Path,
+    output_dir: &Path,
+    options: &InstancesOptions,
+) -> Result<()> {
+    let mut font = load_glyphs_font(glyphs_path)?;
+    font.apply_automatic_alignment();
+
+    let instances: Vec<&glyphs_plist::Instance> = font
+        .instances
+        .iter()
+        .flatten()
+        .filter(|instance| {
+            if options.instance_names.is_empty() {
+                instance.is_exported()
+            } else {
+                options.instance_names.contains(&instance.name)
+            }
+        })
+        .collect();
+    for name in &options.instance_names {
+        if !instances.iter().any(|instance| &instance.name == name) {
+            warn!("Can't find instance {}, skipping.", name);
+        }
+    }
+
+    instances.into_par_iter().try_for_each(|instance| {
+        let ufo = instantiate_ufo(&font, instance)?;
+        let ufo_path = instance_ufo_path(output_dir, &font, instance);
+        save_ufo(&ufo, &ufo_path)
+    })
+}
+
+/// Where the UFO of `instance` goes: "<family>-<instance>.ufo" in
+/// `output_dir`, without spaces, like fontmake names its instances. The
+/// family is the one of the instance, which may differ from the font's.
+fn instance_ufo_path(
+    output_dir: &Path,
+    font: &glyphs_plist::Font,
+    instance: &glyphs_plist::Instance,
+) -> PathBuf {
+    let family_name = instance.family_name(font);
+    let file_name = format!("{}-{}.ufo", family_name, instance.name).replace(' ', "");
+    output_dir.join(file_name)
+}
+
+/// Interpolate a UFO for `instance`: glyphs with brace and bracket layers
+/// taken into account, master metrics, kerning and kerning groups.
+fn instantiate_ufo(
+    font: &glyphs_plist::Font,
+    instance: &glyphs_plist::Instance,
+) -> Result<norad::Font> {
+    let location = font.instance_location(instance);
+    let mut ufo = norad::Font::new();
+
+    font.apply_properties_to_font_info(&mut ufo.font_info);
+    ufo.font_info.family_name = Some(instance.family_name(font).to_string());
+    ufo.font_info.style_name = Some(instance.name.clone());
+    ufo.font_info.units_per_em =
+        norad::fontinfo::NonNegativeIntegerOrFloat::new(font.units_per_em as f64);
+    ufo.font_info.version_major = Some(font.version_major as i32);
+    ufo.font_info.version_minor = u32::try_from(font.version_minor).ok();
+    match font.instance_metrics(&location) {
+        Ok(metrics) => metrics.apply_to_font_info(&mut ufo.font_info),
+        Err(e) => warn!(
+            "Can't interpolate metrics of instance {}: {}",
+            &instance.name, e
+        ),
+    }
+
+    let glyph_order: Vec<Plist> = font
+        .glyphs
+        .iter()
+        .map(|glyph| glyph.glyphname.to_string().into())
+        .collect();
+    ufo.lib
+        .insert("public.glyphOrder".into(), Plist::from(glyph_order).into());
+
+    for glyph in &font.glyphs {
+        let mut layer = match font.instantiate_glyph(glyph, &location) {
+            Ok(layer) => layer,
+            Err(e) => {
+                warn!(
+                    "Can't interpolate glyph {} in instance {}, skipping the glyph: {}",
+                    &glyph.glyphname, &instance.name, e
+                );
+                continue;
+            }
+        };
+        // The corner components are placed on the interpolated outline,
+        // with the shapes of the first master.
+        match font.apply_corner_components(&layer) {
+            Ok(Some(corner_layer)) => layer = corner_layer,
+            Ok(None) => {}
+            Err(e) => warn!(
+                "Can't apply corner components of glyph {}: {}",
+                &glyph.glyphname, e
+            ),
+        }
+        // Instances are for proofing, without hints and background images.
+        layer.hints = None;
+        layer.background_image = None;
+        ufo.default_layer_mut()
+            .insert_glyph(convert_glyphs_glyph_to_ufo_glyph(glyph, &layer)?);
+
+        for (group, prefix) in [
+            (&glyph.right_kerning_group, "public.kern1."),
+            (&glyph.left_kerning_group, "public.kern2."),
+        ] {
+            let Some(group) = group else {
+                continue;
+            };
+            match norad::Name::new(&format!("{}{}", prefix, group)) {
+                Ok(name) => ufo
+                    .groups
+                    .entry(name)
+                    .or_default()
+                    .push(glyph.glyphname.clone()),
+                Err(e) => warn!("Can't name kerning group {}: {:?}", group, e),
+            }
+        }
+    }
+
+    match font.instance_kerning(&location) {
+        Ok(kerning) => {
+            for (first, seconds) in kerning {
+                let Some(first) = kerning_key(&first) else {
+                    continue;
+                };
+                let row = ufo.kerning.entry(first).or_default();
+                for (second, value) in seconds {
+                    if let Some(second) = kerning_key(&second) {
+                        row.insert(second, value.round());
+                    }
+                }
+            }
+        }
+        Err(e) => warn!(
+            "Can't interpolate kerning of instance {}: {}",
+            &instance.name, e
+        ),
+    }
+
+    Ok(ufo)
+}
+
+/// The UFO name of a Glyphs.app kerning key: "@MMK_L_" and "@MMK_R_" groups
+/// become "public.kern1." and "public.kern2." groups.
+fn kerning_key(key: &str) -> Option<norad::Name> {
+    let key = if let Some(group) = key.strip_prefix("@MMK_L_") {
+        format!("public.kern1.{}", group)
+    } else if let Some(group) = key.strip_prefix("@MMK_R_") {
+        format!("public.kern2.{}", group)
+    } else {
+        key.to_string()
+    };
+    norad::Name::new(&key).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use glyphs_plist::FromPlist;
+
+    use super::*;
+
+    #[test]
+    fn instance_ufo_paths() {
+        let font = glyphs_plist::Font {
+            family_name: "My Family".into(),
+            ..Default::default()
+        };
+        let instances: Vec<glyphs_plist::Instance> = FromPlist::from_plist(
+            Plist::parse(
+                r#"(
+                    {name = "Semi Bold";},
+                    {name = Bold; customParameters = ({name = familyName; value = "My Display";});}
+                )"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let paths: Vec<PathBuf> = instances
+            .iter()
+            .map(|instance| instance_ufo_path(Path::new("out"), &font, instance))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("out/MyFamily-SemiBold.ufo"),
+                Path::new("out/MyDisplay-Bold.ufo")
+            ]
+        );
+    }
+}
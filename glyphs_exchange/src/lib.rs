@@ -0,0 +1,29 @@
+//! Converting between UFO sources with a Designspace and Glyphs.app files.
+//!
+//! The `command_*` functions work on files like the command line tool does.
+//! [`convert_to_glyphs`] and [`convert_to_designspace`] work on sources
+//! already in memory, and never touch the disk.
+
+use std::collections::HashMap;
+
+pub mod changes;
+pub mod check;
+pub mod error;
+#[cfg(test)]
+mod fixtures;
+pub mod instantiate;
+pub mod lint;
+mod location;
+pub mod output;
+pub mod to_designspace;
+pub mod to_glyphs;
+
+pub use error::{Error, Result};
+pub use to_designspace::{
+    convert_to_designspace, load_background_images, write_changed_ufos, write_ufos,
+    Glyphs2DesignspaceOptions,
+};
+pub use to_glyphs::{convert_to_glyphs, load_designspace, Ufo2GlyphsOptions};
+
+/// The UFOs of a Designspace, by the file name its sources refer to them by.
+pub type Ufos = HashMap<String, norad::Font>;
@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use glyphs_plist::{LintIssue, LINT_RULES};
+use log::warn;
+
+use crate::error::{glyphs_font_from_plist, read_glyphs_plist, Result};
+use crate::output::{json_string, OutputFormat};
+
+/// Options for linting a Glyphs.app file.
+#[derive(Debug, Default)]
+pub struct LintOptions {
+    /// Rule IDs to skip, on top of those disabled in the font's user data.
+    pub disabled_rules: HashSet<String>,
+    pub format: OutputFormat,
+}
+
+/// Lint a Glyphs.app file and print the issues found. Returns whether there
+/// were none.
+pub fn command_lint(glyphs_path: &Path, options: &LintOptions) -> Result<bool> {
+    let plist = read_glyphs_plist(glyphs_path)?;
+
+    // Invalid glyph names make the font unloadable, so report only those.
+    let mut issues = glyphs_plist::lint_glyph_names(&plist);
+    if issues.is_empty() {
+        let font = glyphs_font_from_plist(glyphs_path, plist)?;
+        let mut disabled_rules = font.disabled_lint_rules();
+        disabled_rules.extend(options.disabled_rules.iter().cloned());
+        for rule in &disabled_rules {
+            if !LINT_RULES.iter().any(|(id, _)| id == rule) {
+                warn!("Unknown lint rule {}, ignoring.", rule);
+            }
+        }
+        issues = font.lint(&disabled_rules);
+    }
+
+    match options.format {
+        OutputFormat::Human => {
+            for issue in &issues {
+                println!("{}", issue);
+            }
+        }
+        OutputFormat::Json => println!("{}", issues_to_json(&issues)),
+    }
+    Ok(issues.is_empty())
+}
+
+fn issues_to_json(issues: &[LintIssue]) -> String {
+    let optional = |value: &Option<String>| match value {
+        Some(value) => json_string(value),
+        None => "null".to_string(),
+    };
+    let objects: Vec<String> = issues
+        .iter()
+        .map(|issue| {
+            format!(
+                r#"{{"rule": {}, "glyph": {}, "layer": {}, "message": {}}}"#,
+                json_string(issue.rule),
+                optional(&issue.glyph),
+                optional(&issue.layer),
+                json_string(&issue.message)
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(", "))
+}
@@ -1,5 +1,7 @@
 use norad::designspace;
 
+use crate::error::{Error, Result};
+
 pub struct Location(Vec<f64>);
 
 type LocationTuple = (
@@ -13,13 +15,19 @@ type LocationTuple = (
 
 impl Location {
     // TODO: Fix reliance on the order of dimensions in the location.
-    pub fn from_dimension(dimension: &[designspace::Dimension]) -> Self {
+    pub fn from_dimension(dimension: &[designspace::Dimension]) -> Result<Self> {
         let locations: Vec<_> = dimension
             .iter()
             .map(|dim| dim.xvalue.unwrap_or(0.0) as f64)
             .collect();
-        assert!(!locations.is_empty() && locations.len() <= 6);
-        Self(locations)
+        if locations.is_empty() || locations.len() > 6 {
+            return Err(Error::Validation(vec![format!(
+                "Location {} has {} axes, but only one to six are supported.",
+                Self(locations.clone()),
+                locations.len()
+            )]));
+        }
+        Ok(Self(locations))
     }
 
     pub fn as_tuple(&self) -> LocationTuple {
@@ -32,6 +40,27 @@ impl Location {
             self.0.get(5).cloned(),
         )
     }
+
+    /// The Euclidean distance to `other` in design coordinates.
+    pub fn distance(&self, other: &Location) -> f64 {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Whether the location lies within the bounding box of `locations` on
+    /// every axis.
+    pub fn is_within(&self, locations: &[Location]) -> bool {
+        self.0.iter().enumerate().all(|(axis, value)| {
+            let values = locations.iter().filter_map(|l| l.0.get(axis));
+            let min = values.clone().copied().fold(f64::INFINITY, f64::min);
+            let max = values.copied().fold(f64::NEG_INFINITY, f64::max);
+            (min..=max).contains(value)
+        })
+    }
 }
 
 /// Render location as a string like Glyphs.app would for brace layers, i.e.
@@ -50,3 +79,24 @@ impl std::fmt::Display for Location {
 }
 
 // TODO: Add test that any input DS location can roundtrip to Location and back.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axis_count() {
+        let dimension = |name: &str| designspace::Dimension {
+            name: name.into(),
+            xvalue: Some(100.0),
+            ..Default::default()
+        };
+        let location = Location::from_dimension(&[dimension("Weight"), dimension("Width")]);
+        assert_eq!(location.unwrap().to_string(), "{100, 100}");
+        assert!(matches!(
+            Location::from_dimension(&[]),
+            Err(Error::Validation(_))
+        ));
+        assert!(Location::from_dimension(&vec![dimension("Weight"); 7]).is_err());
+    }
+}
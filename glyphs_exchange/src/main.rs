@@ -1,18 +1,32 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-
-pub mod location;
-pub mod to_designspace;
-pub mod to_glyphs;
-
+use glyphs_exchange::error::save_glyphs_font;
+use glyphs_exchange::{
+    changes, check, instantiate, lint, output, to_designspace, to_glyphs, Error, Result,
+};
+use log::error;
 use mimalloc::MiMalloc;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+/// The exit codes, see [`Error::exit_code`].
+const EXIT_CODES: &str = "\
+Exit codes:
+  0    success
+  1    checks found problems (check, lint, ufo2glyphs --check-only)
+  2    invalid command line
+  3    a file could not be read or written
+  4    a Glyphs.app file or UFO could not be parsed
+  5    a Designspace could not be loaded or saved, or is missing data
+  6    a location or value could not be mapped between the formats
+  7    the input failed the checks run before converting
+  8    a Glyphs.app file or UFO could not be saved
+  101  a bug in glyphs-exchange";
+
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -28,6 +42,23 @@ enum Commands {
         /// The path to the Glyphs.app file to write (default: next to the input
         /// Designspace).
         glyphs_path: Option<PathBuf>,
+
+        /// The UFO layer to read master layer backgrounds from.
+        #[arg(long, default_value = to_designspace::DEFAULT_BACKGROUND_LAYER_NAME)]
+        background_layer: String,
+
+        /// Only check the Designspace and its UFOs for problems, print them
+        /// and write nothing.
+        #[arg(long)]
+        check_only: bool,
+
+        /// Print the glyphs that would change instead of writing anything.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// How to print the changes of a dry run.
+        #[arg(long, value_enum, default_value = "human")]
+        format: output::OutputFormat,
     },
     Glyphs2ufo {
         /// Source Glyphs.app file to convert.
@@ -37,6 +68,70 @@ enum Commands {
         /// The path to the Designspace file to write (default: next to the input
         /// Glyphs.app).
         designspace_path: Option<PathBuf>,
+
+        /// Decompose the components of these glyphs (comma-separated).
+        #[arg(long, value_delimiter = ',')]
+        decompose: Vec<String>,
+
+        /// Decompose all glyphs that mix contours and components.
+        #[arg(long)]
+        decompose_mixed: bool,
+
+        /// Draw corner, cap and segment components into the contours instead
+        /// of keeping them in the glyph lib (ufo2glyphs cannot restore them).
+        #[arg(long)]
+        apply_corner_components: bool,
+
+        /// The UFO layer to write master layer backgrounds to. Backgrounds of
+        /// other layers go to "<layer>.background".
+        #[arg(long, default_value = to_designspace::DEFAULT_BACKGROUND_LAYER_NAME)]
+        background_layer: String,
+
+        /// Print the glyphs that would change instead of writing anything.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// How to print the changes of a dry run.
+        #[arg(long, value_enum, default_value = "human")]
+        format: output::OutputFormat,
+    },
+    Instances {
+        /// Source Glyphs.app file to interpolate.
+        #[arg(required = true)]
+        glyphs_path: PathBuf,
+
+        /// The directory to write the instance UFOs to (default: next to the
+        /// input Glyphs.app).
+        output_dir: Option<PathBuf>,
+
+        /// Interpolate only these instances (comma-separated names), even if
+        /// they are not exported.
+        #[arg(long, value_delimiter = ',')]
+        instances: Vec<String>,
+    },
+    /// Check that the layers of all glyphs are compatible for interpolation.
+    /// Exits with status 1 if any are not.
+    Check {
+        /// Glyphs.app file to check.
+        #[arg(required = true)]
+        glyphs_path: PathBuf,
+    },
+    /// Lint a Glyphs.app file. Exits with status 1 if there are issues.
+    /// Rules can be disabled per project by listing their IDs under
+    /// "com.daltonmaag.glyphsExchange.disabledLintRules" in the font's user
+    /// data.
+    Lint {
+        /// Glyphs.app file to lint.
+        #[arg(required = true)]
+        glyphs_path: PathBuf,
+
+        /// Skip these rules (comma-separated IDs).
+        #[arg(long, value_delimiter = ',')]
+        disable: Vec<String>,
+
+        /// How to print the issues.
+        #[arg(long, value_enum, default_value = "human")]
+        format: output::OutputFormat,
     },
 }
 
@@ -46,26 +141,114 @@ fn main() {
         .init();
 
     let cli = Cli::parse();
-    match cli.command {
+    match run(cli.command) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            match &e {
+                Error::Validation(problems) => {
+                    for problem in problems {
+                        error!("{}", problem);
+                    }
+                }
+                _ => error!("{}", e),
+            }
+            std::process::exit(e.exit_code());
+        }
+    }
+}
+
+/// Run a command. Returns whether the checks it ran found no problems.
+fn run(command: Commands) -> Result<bool> {
+    match command {
         Commands::Ufo2glyphs {
             designspace_path,
             glyphs_path,
+            background_layer,
+            check_only,
+            dry_run,
+            format,
         } => {
-            let glyphs_font = to_glyphs::command_to_glyphs(&designspace_path);
-
+            if check_only {
+                let problems = to_glyphs::command_preflight(&designspace_path)?;
+                for problem in &problems {
+                    eprintln!("{}", problem);
+                }
+                return Ok(problems.is_empty());
+            }
+            let options = to_glyphs::Ufo2GlyphsOptions {
+                background_layer_name: background_layer,
+            };
             let glyphs_path =
                 glyphs_path.unwrap_or_else(|| designspace_path.with_extension("glyphs"));
-            glyphs_font
-                .save(&glyphs_path)
-                .expect("Failed to save Glyphs file!");
+            if dry_run {
+                let changes = to_glyphs::command_to_glyphs_changes(
+                    &designspace_path,
+                    &glyphs_path,
+                    &options,
+                )?;
+                changes::print_changes(&changes, format);
+                return Ok(true);
+            }
+
+            let glyphs_font = to_glyphs::command_to_glyphs(&designspace_path, &options)?;
+            save_glyphs_font(glyphs_font, &glyphs_path)?;
+            Ok(true)
         }
         Commands::Glyphs2ufo {
             glyphs_path,
             designspace_path,
+            decompose,
+            decompose_mixed,
+            apply_corner_components,
+            background_layer,
+            dry_run,
+            format,
         } => {
             let designspace_path =
                 designspace_path.unwrap_or_else(|| glyphs_path.with_extension("designspace"));
-            to_designspace::command_to_designspace(&glyphs_path, &designspace_path);
+            let options = to_designspace::Glyphs2DesignspaceOptions {
+                decompose_glyphs: decompose.into_iter().collect(),
+                decompose_mixed,
+                apply_corner_components,
+                background_layer_name: background_layer,
+            };
+            if dry_run {
+                let changes = to_designspace::command_to_designspace_changes(
+                    &glyphs_path,
+                    &designspace_path,
+                    &options,
+                )?;
+                changes::print_changes(&changes, format);
+                return Ok(true);
+            }
+            to_designspace::command_to_designspace(&glyphs_path, &designspace_path, &options)?;
+            Ok(true)
+        }
+        Commands::Instances {
+            glyphs_path,
+            output_dir,
+            instances,
+        } => {
+            let output_dir = output_dir
+                .unwrap_or_else(|| glyphs_path.parent().map(PathBuf::from).unwrap_or_default());
+            let options = instantiate::InstancesOptions {
+                instance_names: instances.into_iter().collect(),
+            };
+            instantiate::command_instances(&glyphs_path, &output_dir, &options)?;
+            Ok(true)
+        }
+        Commands::Check { glyphs_path } => check::command_check(&glyphs_path),
+        Commands::Lint {
+            glyphs_path,
+            disable,
+            format,
+        } => {
+            let options = lint::LintOptions {
+                disabled_rules: disable.into_iter().collect(),
+                format,
+            };
+            lint::command_lint(&glyphs_path, &options)
         }
     }
 }
@@ -0,0 +1,43 @@
+//! Printing reports, like lint issues and the changes of dry runs, for
+//! people or as JSON.
+
+/// How to print a report.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One line per item.
+    #[default]
+    Human,
+    /// A JSON array of objects.
+    Json,
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(json_string("a"), r#""a""#);
+        assert_eq!(
+            json_string("\"quoted\"\\\n\t\u{1}"),
+            r#""\"quoted\"\\\n\t\u0001""#
+        );
+    }
+}
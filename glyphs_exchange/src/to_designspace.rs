@@ -1,10 +1,80 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
 
+use glyphs_plist::ToPlist;
 use log::warn;
 use norad::{designspace, Glyph};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
 use rayon::prelude::*;
 
+use crate::changes::{compare_ufos, GlyphChange};
+use crate::error::{load_glyphs_font, parent_dir, save_ufo, Error};
 use crate::location::Location;
+use crate::to_glyphs::load_designspace;
+use crate::Ufos;
+
+/// Options for converting a Glyphs.app file back into the UFO sources.
+#[derive(Debug)]
+pub struct Glyphs2DesignspaceOptions {
+    /// Names of glyphs whose components should be decomposed.
+    pub decompose_glyphs: HashSet<String>,
+    /// Also decompose all glyphs that mix paths and components on any layer,
+    /// for downstream tools that cannot handle mixed glyphs.
+    pub decompose_mixed: bool,
+    /// Draw corner, cap and segment components into the contours. Otherwise,
+    /// they are kept as hints in the glyph lib, like all other hints.
+    pub apply_corner_components: bool,
+    /// The UFO layer to write the backgrounds of master layers to.
+    pub background_layer_name: String,
+}
+
+impl Default for Glyphs2DesignspaceOptions {
+    fn default() -> Self {
+        Self {
+            decompose_glyphs: Default::default(),
+            decompose_mixed: false,
+            apply_corner_components: false,
+            background_layer_name: DEFAULT_BACKGROUND_LAYER_NAME.into(),
+        }
+    }
+}
+
+/// The glyph lib key for the vertical origin of a glyph.
+pub(crate) const VERTICAL_ORIGIN_LIB_KEY: &str = "public.verticalOrigin";
+/// The glyph lib key the Glyphs.app hints of a layer are stored under.
+pub(crate) const HINTS_LIB_KEY: &str = "com.schriftgestaltung.hints";
+/// The glyph lib keys for background image properties UFOs have no place for.
+pub(crate) const BACKGROUND_IMAGE_CROP_LIB_KEY: &str = "com.schriftgestaltung.backgroundImage.crop";
+pub(crate) const BACKGROUND_IMAGE_LOCKED_LIB_KEY: &str =
+    "com.schriftgestaltung.backgroundImage.locked";
+
+/// The glyph lib keys written from Glyphs.app data, which are removed from
+/// UFO glyphs where the data is gone.
+const CONVERTED_LIB_KEYS: &[&str] = &[
+    HINTS_LIB_KEY,
+    BACKGROUND_IMAGE_CROP_LIB_KEY,
+    BACKGROUND_IMAGE_LOCKED_LIB_KEY,
+];
+
+/// The UFO layer backgrounds of master layers go to by default.
+pub const DEFAULT_BACKGROUND_LAYER_NAME: &str = "public.background";
+
+/// The UFO layer the backgrounds of the UFO layer `ufo_layer_name` go to:
+/// `master_background_name` for the default layer, "<name>.background" for
+/// all others.
+pub(crate) fn background_layer_name(
+    ufo_layer_name: Option<&str>,
+    master_background_name: &str,
+) -> String {
+    match ufo_layer_name {
+        Some(name) => format!("{}.background", name),
+        None => master_background_name.to_string(),
+    }
+}
 
 #[derive(Debug)]
 struct Glyphs2DesignspaceContext {
@@ -15,10 +85,13 @@ struct Glyphs2DesignspaceContext {
 }
 
 impl Glyphs2DesignspaceContext {
-    fn from_paths(glyphs_path: &Path, designspace_path: &Path) -> Self {
-        let font = glyphs_plist::Font::load(&glyphs_path).expect("Cannot load Glyphs file");
-        let designspace = designspace::DesignSpaceDocument::load(designspace_path)
-            .expect("Cannot load Designspace");
+    fn new(
+        mut font: glyphs_plist::Font,
+        designspace: &designspace::DesignSpaceDocument,
+    ) -> Result<Self, Error> {
+        // Glyphs.app ignores the stored offsets of automatically aligned
+        // components, so position them like it does before exporting.
+        font.apply_automatic_alignment();
 
         let mut ufo_mapping: HashMap<String, HashMap<String, Option<String>>> = HashMap::new();
         for source in &designspace.sources {
@@ -28,45 +101,161 @@ impl Glyphs2DesignspaceContext {
                 *ufo_mapping
                     .entry(source.filename.clone())
                     .or_default()
-                    .entry(Location::from_dimension(&source.location).to_string())
+                    .entry(Location::from_dimension(&source.location)?.to_string())
                     .or_default() = source.layer.clone();
             } else {
-                let glyphs_master = font
-                    .font_master
-                    .iter()
-                    .find(|m| {
-                        m.name()
-                            == source
-                                .stylename
-                                .as_ref()
-                                .expect("Designspace sources must have a style name")
-                    })
-                    .expect("Cannot find matching Glyphs master for source");
+                let stylename = source.stylename.as_ref().ok_or_else(|| {
+                    Error::Validation(vec![format!("Source {} has no style name.", source.name)])
+                })?;
+                // A source without a master of its own is a sparse UFO, which
+                // takes the brace layers at its location into its default
+                // layer.
+                let key = match font.font_master.iter().find(|m| m.name() == Some(stylename.as_str())) {
+                    Some(glyphs_master) => glyphs_master.id.clone(),
+                    None => Location::from_dimension(&source.location)?.to_string(),
+                };
                 *ufo_mapping
                     .entry(source.filename.clone())
                     .or_default()
-                    .entry(glyphs_master.id.clone())
+                    .entry(key)
                     .or_default() = None;
             }
         }
 
-        Self { font, ufo_mapping }
+        Ok(Self { font, ufo_mapping })
+    }
+}
+
+/// Convert a Glyphs.app file back into the UFO sources of a Designspace,
+/// saving what changed in the UFOs, and the Designspace if its axes changed.
+pub fn command_to_designspace(
+    glyphs_path: &Path,
+    designspace_path: &Path,
+    options: &Glyphs2DesignspaceOptions,
+) -> Result<(), Error> {
+    let font = load_glyphs_font(glyphs_path)?;
+    let images = load_background_images(&font, parent_dir(glyphs_path)?);
+    let (mut designspace, loaded) = load_designspace(designspace_path)?;
+
+    let mut ufos = loaded.clone();
+    let designspace_changed =
+        convert_to_designspace(font, &images, &mut designspace, &mut ufos, options)?;
+
+    write_changed_ufos(designspace_path, &loaded, &ufos)?;
+    if designspace_changed {
+        write_designspace_axes(designspace_path, &designspace.axes)?;
     }
+    Ok(())
+}
+
+/// Convert a Glyphs.app file back into the UFO sources of a Designspace
+/// without saving anything, and report the glyphs that would change.
+pub fn command_to_designspace_changes(
+    glyphs_path: &Path,
+    designspace_path: &Path,
+    options: &Glyphs2DesignspaceOptions,
+) -> Result<Vec<GlyphChange>, Error> {
+    let font = load_glyphs_font(glyphs_path)?;
+    let images = load_background_images(&font, parent_dir(glyphs_path)?);
+    let (mut designspace, ufos) = load_designspace(designspace_path)?;
+
+    let mut converted = ufos.clone();
+    convert_to_designspace(font, &images, &mut designspace, &mut converted, options)?;
+    Ok(compare_ufos(&ufos, &converted))
 }
 
-pub fn command_to_designspace(glyphs_path: &Path, designspace_path: &Path) {
-    let context = Glyphs2DesignspaceContext::from_paths(glyphs_path, designspace_path);
+/// Read the background images of all layers of a Glyphs.app file in
+/// `glyphs_dir`, by their path in the layers. Images that cannot be read are
+/// left out.
+pub fn load_background_images(
+    font: &glyphs_plist::Font,
+    glyphs_dir: &Path,
+) -> HashMap<String, Vec<u8>> {
+    let image_paths: HashSet<&str> = font
+        .glyphs
+        .iter()
+        .flat_map(|glyph| glyph.layers.iter())
+        .filter_map(|layer| layer.background_image.as_ref())
+        .map(|image| image.image_path.as_str())
+        .collect();
+    image_paths
+        .into_iter()
+        .filter_map(|image_path| match read_png(&glyphs_dir.join(image_path)) {
+            Ok(data) => Some((image_path.to_string(), data)),
+            Err(e) => {
+                warn!("Can't copy background image: {}", e);
+                None
+            }
+        })
+        .collect()
+}
 
-    context
+/// Convert a Glyphs.app file into the UFO sources of a Designspace in
+/// memory, by source file name. Background images are taken from `images`,
+/// by their path in the layers, and dropped if missing. Returns whether the
+/// Designspace axes changed.
+pub fn convert_to_designspace(
+    font: glyphs_plist::Font,
+    images: &HashMap<String, Vec<u8>>,
+    designspace: &mut designspace::DesignSpaceDocument,
+    ufos: &mut Ufos,
+    options: &Glyphs2DesignspaceOptions,
+) -> Result<bool, Error> {
+    let context = Glyphs2DesignspaceContext::new(font, designspace)?;
+    let axes = context
+        .font
+        .axis_definitions()
+        .map_err(|e| Error::Mapping(format!("Invalid axes in Glyphs file: {}", e)))?;
+    let missing: Vec<String> = context
         .ufo_mapping
-        .into_par_iter()
-        .for_each(|(ufo_path, layer_ids)| {
-            let ufo_path = designspace_path.parent().unwrap().join(ufo_path);
-            let mut ufo = norad::Font::load(&ufo_path).expect("Cannot load UFO");
+        .keys()
+        .filter(|filename| !ufos.contains_key(*filename))
+        .map(|filename| format!("UFO {} is not loaded.", filename))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::Validation(missing));
+    }
+
+    ufos
+        .par_iter_mut()
+        .try_for_each(|(filename, ufo)| {
+            let Some(layer_ids) = context.ufo_mapping.get(filename) else {
+                return Ok(());
+            };
+
+            // The UFO's font-wide data comes from the master it is the
+            // default layer of. Sparse UFOs have none and are left as is.
+            let master = layer_ids
+                .iter()
+                .filter(|(_, ufo_layer_name)| ufo_layer_name.is_none())
+                .find_map(|(id, _)| context.font.font_master.iter().find(|m| &m.id == id));
+            if let Some(master) = master {
+                context
+                    .font
+                    .master_metrics(master)
+                    .apply_to_font_info(&mut ufo.font_info);
+                context
+                    .font
+                    .postscript_hinting(master)
+                    .apply_to_font_info(&mut ufo.font_info);
+                context
+                    .font
+                    .vertical_metrics(master)
+                    .apply_to_font_info(&mut ufo.font_info);
+                context
+                    .font
+                    .apply_properties_to_font_info(&mut ufo.font_info);
+            }
+            // Background images to copy into the UFO, by file name, with the
+            // path they come from.
+            let mut ufo_images: HashMap<PathBuf, (&str, &Vec<u8>)> = HashMap::new();
 
             for glyph in context.font.glyphs.iter() {
+                let decompose = options.decompose_glyphs.contains(glyph.glyphname.as_str())
+                    || (options.decompose_mixed && glyph.has_mixed_layers());
+
                 for layer in glyph.layers.iter() {
-                    let (ufo_layer, is_default) = {
+                    let (ufo_layer, is_default, background_name) = {
                         // TODO: Adapt for Glyphs 3 where a brace layer could be
                         // identified by position.
                         // TODO: Deal with bracket (and other functional) layers
@@ -77,12 +266,20 @@ pub fn command_to_designspace(glyphs_path: &Path, designspace_path: &Path) {
                             Some(ufo_layer_name) => {
                                 let is_default = ufo.layers.default_layer().name().as_str() == ufo_layer_name.as_str();
                                 let Some(ufo_layer) = ufo.layers.get_mut(ufo_layer_name) else {
-                                    warn!("Can't find layer {} in UFO {}, skipping.", ufo_layer_name, ufo_path.display());
+                                    warn!("Can't find layer {} in UFO {}, skipping.", ufo_layer_name, filename);
                                     continue;
                                 };
-                                (ufo_layer, is_default)
+                                let background_name = background_layer_name(
+                                    (!is_default).then_some(ufo_layer_name.as_str()),
+                                    &options.background_layer_name,
+                                );
+                                (ufo_layer, is_default, background_name)
                             },
-                            None => (ufo.default_layer_mut(), true),
+                            None => (
+                                ufo.default_layer_mut(),
+                                true,
+                                background_layer_name(None, &options.background_layer_name),
+                            ),
                         }
                     };
 
@@ -91,10 +288,41 @@ pub fn command_to_designspace(glyphs_path: &Path, designspace_path: &Path) {
                             Some(name) => format!("layer '{}'", name),
                             None => "default layer".to_string(),
                         };
-                        warn!("Can't find glyph {} in UFO {}, {}, skipping.", &glyph.glyphname, ufo_path.display(), layer_name);
+                        warn!("Can't find glyph {} in UFO {}, {}, skipping.", &glyph.glyphname, filename, layer_name);
                         continue;
                     };
-                    let converted_glyph = convert_glyphs_glyph_to_ufo_glyph(glyph, layer);
+
+                    // UFOs have no smart components, so write out their
+                    // instances at the stored piece values.
+                    let smart_layer = context
+                        .font
+                        .decompose_smart_components(layer)
+                        .unwrap_or_else(|e| {
+                            warn!("Can't instantiate smart components of glyph {}: {}", &glyph.glyphname, e);
+                            None
+                        });
+                    let layer = smart_layer.as_ref().unwrap_or(layer);
+
+                    let corner_layer = if options.apply_corner_components {
+                        context
+                            .font
+                            .apply_corner_components(layer)
+                            .unwrap_or_else(|e| {
+                                warn!("Can't apply corner components of glyph {}: {}", &glyph.glyphname, e);
+                                None
+                            })
+                    } else {
+                        None
+                    };
+                    let layer = corner_layer.as_ref().unwrap_or(layer);
+
+                    let mut converted_glyph = if decompose {
+                        convert_glyphs_glyph_to_ufo_glyph(glyph, &context.font.decompose(layer))?
+                    } else {
+                        convert_glyphs_glyph_to_ufo_glyph(glyph, layer)?
+                    };
+
+                    adopt_identifiers(&mut converted_glyph, ufo_glyph);
 
                     // Codepoints should only go into the default layer.
                     if is_default {
@@ -104,45 +332,416 @@ pub fn command_to_designspace(glyphs_path: &Path, designspace_path: &Path) {
                     }
 
                     ufo_glyph.width = converted_glyph.width;
+                    // Layers without a vertical advance keep the UFO's height.
+                    if let Some(height) = layer.vert_width {
+                        ufo_glyph.height = height;
+                    }
                     ufo_glyph.anchors = converted_glyph.anchors;
                     ufo_glyph.contours = converted_glyph.contours;
                     ufo_glyph.components = converted_glyph.components;
+                    for &key in CONVERTED_LIB_KEYS {
+                        match converted_glyph.lib.remove(key) {
+                            Some(value) => ufo_glyph.lib.insert(key.to_string(), value),
+                            None => ufo_glyph.lib.remove(key),
+                        };
+                    }
+
+                    let vertical_origin = context
+                        .font
+                        .font_master
+                        .iter()
+                        .find(|m| m.id == layer.master_id())
+                        .and_then(|master| context.font.default_vertical_origin(master))
+                        .and_then(|default| layer.vertical_origin(default));
+                    match vertical_origin {
+                        Some(origin) => ufo_glyph.lib.insert(VERTICAL_ORIGIN_LIB_KEY.into(), origin.into()),
+                        None => ufo_glyph.lib.remove(VERTICAL_ORIGIN_LIB_KEY),
+                    };
+
+                    if let (Some(image), Some(ufo_image)) = (&layer.background_image, &converted_glyph.image) {
+                        let ufo_image = images.get_key_value(&image.image_path).and_then(|(image_path, data)| {
+                            let file_name = unique_image_name(&ufo_images, ufo_image.file_name(), image_path);
+                            ufo_images.insert(file_name.clone(), (image_path, data));
+                            norad::Image::new(file_name, ufo_image.color.clone(), ufo_image.transform).ok()
+                        });
+                        converted_glyph.image = ufo_image;
+                    }
+                    ufo_glyph.image = converted_glyph.image;
+
+                    match layer.background_layer() {
+                        Some(background) => {
+                            let mut background_glyph = convert_glyphs_glyph_to_ufo_glyph(glyph, &background)?;
+                            background_glyph.codepoints.clear();
+                            if let Some(existing) = ufo.layers.get(&background_name).and_then(|l| l.get_glyph(&glyph.glyphname)) {
+                                adopt_identifiers(&mut background_glyph, existing);
+                            }
+                            ufo.layers
+                                .get_or_create_layer(&background_name)
+                                .map_err(|e| Error::Mapping(format!("Invalid background layer name {}: {}", background_name, e)))?
+                                .insert_glyph(background_glyph);
+                        }
+                        None => {
+                            if let Some(background_layer) = ufo.layers.get_mut(&background_name) {
+                                background_layer.remove_glyph(&glyph.glyphname);
+                            }
+                        }
+                    }
+                }
+            }
+
+            for (file_name, (_, data)) in ufo_images {
+                if let Err(e) = ufo.images.insert(file_name.clone(), data.clone()) {
+                    warn!("Can't add image {} to UFO {}: {:?}", file_name.display(), filename, e);
                 }
             }
+            Ok(())
+        })?;
+
+    Ok(update_designspace_axes(designspace, &axes))
+}
+
+/// Save the UFOs of a Designspace, by source file name, and normalize them
+/// with ufonormalizer if it is installed.
+pub fn write_ufos(designspace_path: &Path, ufos: &Ufos) -> Result<(), Error> {
+    let designspace_dir = parent_dir(designspace_path)?;
+    ufos.iter().try_for_each(|(filename, ufo)| {
+        let ufo_path = designspace_dir.join(filename);
+        write_ufo(ufo, &ufo_path)?;
+        run_ufonormalizer(&ufo_path).map_err(|e| Error::io(&ufo_path, e))
+    })
+}
+
+/// Save the UFOs of a Designspace like [`write_ufos`], but only the glyph
+/// files and plists that differ from the UFOs as `loaded`, leaving all other
+/// files untouched. UFOs whose glyph sets, layers, groups, kerning, features
+/// or background images changed are saved whole.
+pub fn write_changed_ufos(
+    designspace_path: &Path,
+    loaded: &Ufos,
+    ufos: &Ufos,
+) -> Result<(), Error> {
+    let designspace_dir = parent_dir(designspace_path)?;
+    ufos.iter().try_for_each(|(filename, ufo)| {
+        let ufo_path = designspace_dir.join(filename);
+        let written = match loaded.get(filename) {
+            Some(loaded) if has_same_structure(loaded, ufo) => {
+                write_changed_files(loaded, ufo, &ufo_path)?
+            }
+            _ => {
+                write_ufo(ufo, &ufo_path)?;
+                true
+            }
+        };
+        if written {
+            run_ufonormalizer(&ufo_path).map_err(|e| Error::io(&ufo_path, e))?;
+        }
+        Ok(())
+    })
+}
+
+/// Save a UFO, but preserve the metainfo.plist, because it's uninteresting
+/// and changing it increases git noise.
+fn write_ufo(ufo: &norad::Font, ufo_path: &Path) -> Result<(), Error> {
+    let metainfo_path = ufo_path.join("metainfo.plist");
+    let metainfo = fs::read(&metainfo_path).map_err(|e| Error::io(&metainfo_path, e))?;
+    save_ufo(ufo, ufo_path)?;
+    fs::write(&metainfo_path, metainfo).map_err(|e| Error::io(&metainfo_path, e))
+}
+
+/// Whether only glyphs, the font info or the lib differ between the UFOs,
+/// which can be written file by file. Images are stored lazily and not
+/// compared, so UFOs with background images never are.
+fn has_same_structure(loaded: &norad::Font, ufo: &norad::Font) -> bool {
+    let glyph_names = |layer: &norad::Layer| -> HashSet<String> {
+        layer.iter().map(|glyph| glyph.name().to_string()).collect()
+    };
+    let has_images = ufo
+        .layers
+        .iter()
+        .any(|layer| layer.iter().any(|glyph| glyph.image.is_some()));
+    !has_images
+        && loaded.groups == ufo.groups
+        && loaded.kerning == ufo.kerning
+        && loaded.features == ufo.features
+        && loaded.layers.len() == ufo.layers.len()
+        && ufo
+            .layers
+            .iter()
+            .all(|layer| match loaded.layers.get(layer.name().as_str()) {
+                Some(loaded_layer) => {
+                    loaded_layer.path() == layer.path()
+                        && loaded_layer.color == layer.color
+                        && loaded_layer.lib == layer.lib
+                        && glyph_names(loaded_layer) == glyph_names(layer)
+                }
+                None => false,
+            })
+}
 
-            // Save the UFO, but preserve the metainfo.plist, because it's
-            // uninteresting and changing it increases git noise.
-            let metainfo_path = ufo_path.join("metainfo.plist");
-            let metainfo = fs::read(&metainfo_path).expect("Cannot read metainfo.plist");
-            ufo.save(&ufo_path).expect("Cannot save UFO");
-            fs::write(metainfo_path, metainfo).expect("Cannot write metainfo.plist");
+/// Write the glyph files, fontinfo.plist and lib.plist of a UFO that differ
+/// from the UFO as `loaded`. Returns whether anything was written.
+fn write_changed_files(
+    loaded: &norad::Font,
+    ufo: &norad::Font,
+    ufo_path: &Path,
+) -> Result<bool, Error> {
+    let mut changed_plists = Vec::new();
+    if loaded.font_info != ufo.font_info {
+        changed_plists.push("fontinfo.plist");
+    }
+    if loaded.lib != ufo.lib {
+        changed_plists.push("lib.plist");
+    }
+    let mut written = !changed_plists.is_empty();
+    if written {
+        write_plists(ufo, ufo_path, &changed_plists)?;
+    }
 
-            run_ufonormalizer(&ufo_path)
-                .map_err(|e| format!("ufonormalizer failed on {}: {:?}", ufo_path.display(), e))
-                .unwrap();
+    for layer in ufo.layers.iter() {
+        let Some(loaded_layer) = loaded.layers.get(layer.name().as_str()) else {
+            continue;
+        };
+        let changed: Vec<&Glyph> = layer
+            .iter()
+            .filter(|glyph| loaded_layer.get_glyph(glyph.name().as_str()) != Some(*glyph))
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        // The glyph names are the same, so the file names in the layer's
+        // contents.plist still hold.
+        let layer_path = ufo_path.join(layer.path());
+        let contents_path = layer_path.join("contents.plist");
+        let contents =
+            plist::Value::from_file(&contents_path).map_err(|e| Error::parse(&contents_path, e))?;
+        for glyph in changed {
+            let file_name = contents
+                .as_dictionary()
+                .and_then(|contents| contents.get(glyph.name().as_str()))
+                .and_then(plist::Value::as_string)
+                .ok_or_else(|| Error::Parse {
+                    path: contents_path.clone(),
+                    message: format!("no file for glyph {}", glyph.name().as_str()),
+                })?;
+            let glif_path = layer_path.join(file_name);
+            let data = glyph.encode_xml().map_err(|e| Error::save(&glif_path, e))?;
+            fs::write(&glif_path, data).map_err(|e| Error::io(&glif_path, e))?;
+            written = true;
+        }
+    }
+    Ok(written)
+}
+
+/// Write the font-wide plists `file_names` of a UFO exactly like a full
+/// save would, by saving the font info and lib with norad to a temporary
+/// UFO and copying them over. Plists norad leaves out when empty are removed.
+fn write_plists(ufo: &norad::Font, ufo_path: &Path, file_names: &[&str]) -> Result<(), Error> {
+    let mut plists = norad::Font::new();
+    plists.font_info = ufo.font_info.clone();
+    plists.lib = ufo.lib.clone();
+    let temp_path =
+        std::env::temp_dir().join(format!("glyphs-exchange-{}.ufo", uuid::Uuid::new_v4()));
+    let result = save_ufo(&plists, &temp_path).and_then(|()| {
+        file_names.iter().try_for_each(|file_name| {
+            let path = ufo_path.join(file_name);
+            let temp_file_path = temp_path.join(file_name);
+            if temp_file_path.exists() {
+                fs::copy(&temp_file_path, &path).map(drop)
+            } else if path.exists() {
+                fs::remove_file(&path)
+            } else {
+                Ok(())
+            }
+            .map_err(|e| Error::io(&path, e))
+        })
+    });
+    // Leftovers in the temporary directory do no harm.
+    let _ = fs::remove_dir_all(&temp_path);
+    result
+}
+
+/// Bring the Designspace axes in line with the Glyphs.app axes, matched by
+/// tag or name. Returns whether anything changed.
+fn update_designspace_axes(
+    designspace: &mut designspace::DesignSpaceDocument,
+    axes: &[glyphs_plist::AxisDefinition],
+) -> bool {
+    let original_axes = designspace.axes.clone();
+    for axis in axes {
+        let Some(ds_axis) = designspace
+            .axes
+            .iter_mut()
+            .find(|a| a.tag == axis.tag || a.name == axis.name)
+        else {
+            warn!("Axis {} is not in the Designspace, skipping.", axis.name);
+            continue;
+        };
+        ds_axis.hidden = axis.hidden;
+        ds_axis.minimum = Some(axis.minimum as f32);
+        ds_axis.default = axis.default as f32;
+        ds_axis.maximum = Some(axis.maximum as f32);
+        ds_axis.map = (!axis.mapping.is_empty()).then(|| {
+            axis.mapping
+                .iter()
+                .map(|&(user, design)| designspace::AxisMapping {
+                    input: user as f32,
+                    output: design as f32,
+                })
+                .collect()
         });
+    }
+    designspace.axes != original_axes
+}
+
+/// Write `axes` into the Designspace file at `path`. norad cannot write
+/// Designspaces, so everything else is copied over from the file as it is.
+fn write_designspace_axes(path: &Path, axes: &[designspace::Axis]) -> Result<(), Error> {
+    let xml = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let xml = replace_designspace_axes(&xml, axes).map_err(|e| Error::designspace(path, e))?;
+    fs::write(path, xml).map_err(|e| Error::io(path, e))
+}
+
+/// Replace the contents of the `<axes>` element of a Designspace with `axes`,
+/// indented and with line endings like the element. Elements norad does not
+/// read, like axis labels, would be lost, so they are refused.
+fn replace_designspace_axes(xml: &str, axes: &[designspace::Axis]) -> Result<String, String> {
+    let newline = if xml.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    let mut replaced = false;
+    loop {
+        let position = reader.buffer_position();
+        let event = reader.read_event().map_err(|e| e.to_string())?;
+        match &event {
+            Event::Start(start) | Event::Empty(start)
+                if start.name().as_ref() == b"axes" && !replaced =>
+            {
+                if let Event::Start(_) = event {
+                    let content = reader
+                        .read_to_end(start.name())
+                        .map_err(|e| e.to_string())?;
+                    check_axes_content(&xml[content])?;
+                }
+                let line_start = xml[..position].rfind('\n').map_or(0, |i| i + 1);
+                let indent = &xml[line_start..position];
+                let indent = if indent.trim().is_empty() { indent } else { "" };
+                write_axes(&mut writer, axes, newline, indent).map_err(|e| e.to_string())?;
+                replaced = true;
+            }
+            Event::Eof => break,
+            _ => writer.write_event(event).map_err(|e| e.to_string())?,
+        }
+    }
+    if !replaced {
+        return Err("cannot find the <axes> element".into());
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+/// Refuse the contents of an `<axes>` element if it has elements other than
+/// those norad reads.
+fn check_axes_content(content: &str) -> Result<(), String> {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(element) | Event::Empty(element)
+                if !matches!(element.name().as_ref(), b"axis" | b"map") =>
+            {
+                return Err(format!(
+                    "cannot rewrite axes containing <{}> elements",
+                    String::from_utf8_lossy(element.name().as_ref())
+                ));
+            }
+            Event::Eof => return Ok(()),
+            _ => (),
+        }
+    }
+}
+
+/// Write an `<axes>` element with `axes`, starting at `indent` and indenting
+/// by as much again, or by two spaces at the top level.
+fn write_axes(
+    writer: &mut Writer<Vec<u8>>,
+    axes: &[designspace::Axis],
+    newline: &str,
+    indent: &str,
+) -> quick_xml::Result<()> {
+    let step = if indent.is_empty() { "  " } else { indent };
+    let line = |depth: usize| {
+        Event::Text(BytesText::from_escaped(format!(
+            "{}{}{}",
+            newline,
+            indent,
+            step.repeat(depth)
+        )))
+    };
+
+    writer.write_event(Event::Start(BytesStart::new("axes")))?;
+    for axis in axes {
+        let mut element = BytesStart::new("axis");
+        element.push_attribute(("tag", axis.tag.as_str()));
+        element.push_attribute(("name", axis.name.as_str()));
+        if let Some(values) = &axis.values {
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            element.push_attribute(("values", values.join(" ").as_str()));
+        }
+        if let Some(minimum) = axis.minimum {
+            element.push_attribute(("minimum", minimum.to_string().as_str()));
+        }
+        if let Some(maximum) = axis.maximum {
+            element.push_attribute(("maximum", maximum.to_string().as_str()));
+        }
+        element.push_attribute(("default", axis.default.to_string().as_str()));
+        if axis.hidden {
+            element.push_attribute(("hidden", "1"));
+        }
+
+        writer.write_event(line(1))?;
+        match axis.map.as_deref() {
+            Some(mapping) if !mapping.is_empty() => {
+                writer.write_event(Event::Start(element))?;
+                for map in mapping {
+                    let mut element = BytesStart::new("map");
+                    element.push_attribute(("input", map.input.to_string().as_str()));
+                    element.push_attribute(("output", map.output.to_string().as_str()));
+                    writer.write_event(line(2))?;
+                    writer.write_event(Event::Empty(element))?;
+                }
+                writer.write_event(line(1))?;
+                writer.write_event(Event::End(BytesEnd::new("axis")))?;
+            }
+            _ => writer.write_event(Event::Empty(element))?,
+        }
+    }
+    writer.write_event(line(0))?;
+    writer.write_event(Event::End(BytesEnd::new("axes")))
 }
 
-fn convert_glyphs_glyph_to_ufo_glyph(
+pub(crate) fn convert_glyphs_glyph_to_ufo_glyph(
     glyph: &glyphs_plist::Glyph,
     layer: &glyphs_plist::Layer,
-) -> norad::Glyph {
-    let mut ufo_glyph = Glyph::new("converted_glyph");
+) -> Result<norad::Glyph, Error> {
+    let mut ufo_glyph = Glyph::new(&glyph.glyphname);
 
-    // TODO: Figure out height: only interesting if there is a vertical origin?
     ufo_glyph.width = layer.width;
+    if let Some(height) = layer.vert_width {
+        ufo_glyph.height = height;
+    }
 
     if let Some(unicodes) = &glyph.unicode {
         ufo_glyph.codepoints = unicodes.clone();
     }
 
-    ufo_glyph.anchors.extend(
-        layer
-            .anchors
-            .iter()
-            .flat_map(|anchors| anchors.iter())
-            .map(|anchor| anchor.try_into().expect("Cannot convert anchor name")),
-    );
+    for anchor in layer.anchors.iter().flatten() {
+        let ufo_anchor = anchor.try_into().map_err(|e| {
+            Error::Mapping(format!(
+                "Cannot convert anchor {} of glyph {}: {}",
+                anchor.name, glyph.glyphname, e
+            ))
+        })?;
+        ufo_glyph.anchors.push(ufo_anchor);
+    }
     ufo_glyph.contours.extend(
         layer
             .paths
@@ -150,15 +749,129 @@ fn convert_glyphs_glyph_to_ufo_glyph(
             .flat_map(|paths| paths.iter())
             .map(|path| path.into()),
     );
-    ufo_glyph.components.extend(
-        layer
-            .components
+    for component in layer.components.iter().flatten() {
+        let ufo_component = component.try_into().map_err(|e| {
+            Error::Mapping(format!(
+                "Cannot convert component {} of glyph {}: {}",
+                component.name, glyph.glyphname, e
+            ))
+        })?;
+        ufo_glyph.components.push(ufo_component);
+    }
+    if let Some(hints) = &layer.hints {
+        ufo_glyph
+            .lib
+            .insert(HINTS_LIB_KEY.into(), hints.clone().to_plist().into());
+    }
+    if let Some(image) = &layer.background_image {
+        // The image is copied into the UFO's images directory under its file
+        // name. UFOs have no opacity, so use a white tint with it instead.
+        let color = image
+            .alpha
+            .and_then(|alpha| norad::Color::new(1.0, 1.0, 1.0, alpha as f64 / 100.0).ok());
+        let transform = image.transform.unwrap_or_default().into();
+        ufo_glyph.image = Path::new(&image.image_path)
+            .file_name()
+            .and_then(|file_name| match norad::Image::new(file_name.into(), color, transform) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    warn!("Dropping background image {} of glyph {}: {:?}", image.image_path, glyph.glyphname, e);
+                    None
+                }
+            });
+        if let Some(crop) = &image.crop {
+            ufo_glyph
+                .lib
+                .insert(BACKGROUND_IMAGE_CROP_LIB_KEY.into(), crop.clone().into());
+        }
+        if let Some(locked) = image.locked {
+            ufo_glyph
+                .lib
+                .insert(BACKGROUND_IMAGE_LOCKED_LIB_KEY.into(), locked.into());
+        }
+    }
+
+    Ok(ufo_glyph)
+}
+
+/// The file name for the image at `image_path` in the UFO images directory,
+/// which is flat: `file_name`, or with a number appended if an image from
+/// another path in `taken` has it.
+fn unique_image_name(taken: &HashMap<PathBuf, (&str, &Vec<u8>)>, file_name: &Path, image_path: &str) -> PathBuf {
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file_name.extension().unwrap_or_default().to_string_lossy();
+    let mut candidate = file_name.to_path_buf();
+    let mut number = 1;
+    while matches!(taken.get(&candidate), Some((path, _)) if *path != image_path) {
+        number += 1;
+        candidate = PathBuf::from(format!("{}-{}.{}", stem, number, extension));
+    }
+    candidate
+}
+
+/// Give contours without identifiers, and their points, the identifiers of
+/// a contour of `existing` with the same points, so that the identifiers
+/// survive a round-trip through Glyphs.app. Contours that match none, e.g.
+/// because they were edited, get no identifiers.
+fn adopt_identifiers(glyph: &mut norad::Glyph, existing: &norad::Glyph) {
+    // Adding zero turns -0.0 into 0.0.
+    let key = |contour: &norad::Contour| -> Vec<(u64, u64)> {
+        contour
+            .points
             .iter()
-            .flat_map(|components| components.iter())
-            .map(|component| component.try_into().expect("Cannot convert component name")),
-    );
+            .map(|p| ((p.x + 0.0).to_bits(), (p.y + 0.0).to_bits()))
+            .collect()
+    };
 
-    ufo_glyph
+    let mut used: HashSet<String> = glyph
+        .contours
+        .iter()
+        .flat_map(|contour| {
+            contour
+                .identifier()
+                .into_iter()
+                .chain(contour.points.iter().filter_map(|point| point.identifier()))
+        })
+        .map(|identifier| identifier.as_str().to_string())
+        .collect();
+    let mut candidates: HashMap<Vec<(u64, u64)>, Vec<&norad::Contour>> = HashMap::new();
+    for contour in &existing.contours {
+        candidates.entry(key(contour)).or_default().push(contour);
+    }
+
+    // Identical contours take the candidates in order, and identifiers are
+    // only adopted if not used yet, to keep them unique.
+    let mut take = |identifier: Option<&norad::Identifier>| {
+        let identifier = identifier?;
+        used.insert(identifier.as_str().to_string()).then(|| identifier.clone())
+    };
+    for contour in glyph.contours.iter_mut() {
+        let matching = match candidates.get_mut(&key(contour)) {
+            Some(matching) if !matching.is_empty() => matching.remove(0),
+            _ => continue,
+        };
+        if contour.identifier().is_none() {
+            if let Some(identifier) = take(matching.identifier()) {
+                contour.replace_identifier(identifier);
+            }
+        }
+        for (point, existing) in contour.points.iter_mut().zip(&matching.points) {
+            if point.identifier().is_none() {
+                if let Some(identifier) = take(existing.identifier()) {
+                    point.replace_identifier(identifier);
+                }
+            }
+        }
+    }
+}
+
+/// Read an image for the UFO images directory, which only allows PNG files.
+fn read_png(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Err(format!("{} is not a PNG image", path.display()));
+    }
+    Ok(data)
 }
 
 fn run_ufonormalizer(ufo_path: &Path) -> Result<(), std::io::Error> {
@@ -181,3 +894,331 @@ fn run_ufonormalizer(ufo_path: &Path) -> Result<(), std::io::Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::fixtures;
+    use crate::to_glyphs::{convert_to_glyphs, Ufo2GlyphsOptions};
+
+    const SQUARE: &[(f64, f64)] = &[(100.0, 0.0), (400.0, 0.0), (400.0, 300.0), (100.0, 300.0)];
+    const TRIANGLE: &[(f64, f64)] = &[(0.0, 0.0), (500.0, 0.0), (250.0, 400.0)];
+
+    #[test]
+    fn backgrounds_roundtrip() {
+        let (designspace, mut ufos) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        let regular = fixtures::ufo_filename("Regular");
+        let background = fixtures::ufo_glyph("a", 500.0, TRIANGLE);
+        ufos.get_mut(&regular)
+            .unwrap()
+            .layers
+            .new_layer(DEFAULT_BACKGROUND_LAYER_NAME)
+            .unwrap()
+            .insert_glyph(background.clone());
+
+        let font = convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        let has_background: Vec<bool> = font.glyphs[0]
+            .layers
+            .iter()
+            .map(|layer| layer.background_layer().is_some())
+            .collect();
+        assert_eq!(has_background, [true, false]);
+
+        // Sync into UFOs without background layers, which glyphs2ufo has to
+        // create.
+        let (mut designspace, mut synced) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        convert_to_designspace(
+            font,
+            &HashMap::new(),
+            &mut designspace,
+            &mut synced,
+            &Glyphs2DesignspaceOptions::default(),
+        )
+        .unwrap();
+        let restored = synced[&regular]
+            .layers
+            .get(DEFAULT_BACKGROUND_LAYER_NAME)
+            .and_then(|layer| layer.get_glyph("a"))
+            .unwrap();
+        assert_eq!(fixtures::points(restored), fixtures::points(&background));
+        assert!(synced[&fixtures::ufo_filename("Bold")]
+            .layers
+            .get(DEFAULT_BACKGROUND_LAYER_NAME)
+            .is_none());
+    }
+
+    #[test]
+    fn background_images() {
+        let glyphs = vec![
+            fixtures::ufo_glyph("a", 500.0, SQUARE),
+            fixtures::ufo_glyph("b", 500.0, SQUARE),
+        ];
+        let (mut designspace, mut ufos) = fixtures::masters(glyphs);
+        let mut font =
+            convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        let crop = glyphs_plist::Plist::String("{{0, 0}, {10, 10}}".into());
+        for glyph in &mut font.glyphs {
+            glyph.layers[0].background_image = Some(glyphs_plist::BackgroundImage {
+                image_path: format!("{}/sketch.png", glyph.glyphname),
+                crop: Some(crop.clone()),
+                alpha: Some(50),
+                ..Default::default()
+            });
+        }
+        // Images that cannot be read are dropped.
+        font.glyphs[0].layers[1].background_image = Some(glyphs_plist::BackgroundImage {
+            image_path: "missing.png".into(),
+            ..Default::default()
+        });
+        let png = |content: &[u8]| [b"\x89PNG\r\n\x1a\n", content].concat();
+        let images = HashMap::from([
+            ("a/sketch.png".to_string(), png(b"a")),
+            ("b/sketch.png".to_string(), png(b"b")),
+        ]);
+        convert_to_designspace(
+            font,
+            &images,
+            &mut designspace,
+            &mut ufos,
+            &Glyphs2DesignspaceOptions::default(),
+        )
+        .unwrap();
+
+        // The UFO images directory is flat, so the second sketch.png is
+        // renamed.
+        let regular = &ufos[&fixtures::ufo_filename("Regular")];
+        let image = |name: &str| regular.get_glyph(name).unwrap().image.as_ref().unwrap();
+        assert_eq!(image("a").file_name(), Path::new("sketch.png"));
+        assert_eq!(image("b").file_name(), Path::new("sketch-2.png"));
+        assert_eq!(image("a").color.as_ref().unwrap().channels().3, 0.5);
+        assert!(regular.images.get(Path::new("sketch.png")).is_some());
+        assert!(regular.images.get(Path::new("sketch-2.png")).is_some());
+        let bold = &ufos[&fixtures::ufo_filename("Bold")];
+        assert!(bold.get_glyph("a").unwrap().image.is_none());
+
+        let font = convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        let image = font.glyphs[1].layers[0].background_image.as_ref().unwrap();
+        assert_eq!(image.image_path, "Test-Regular.ufo/images/sketch-2.png");
+        assert_eq!(image.crop, Some(crop));
+        assert_eq!(image.alpha, Some(50));
+    }
+
+    #[test]
+    fn adopt_identifiers_by_contour() {
+        let identifier = |id: &str| norad::Identifier::new(id).unwrap();
+        let mut existing = fixtures::ufo_glyph("a", 500.0, SQUARE);
+        existing
+            .contours
+            .extend(fixtures::ufo_glyph("a", 500.0, TRIANGLE).contours);
+        for (i, contour) in existing.contours.iter_mut().enumerate() {
+            contour.replace_identifier(identifier(&format!("contour{}", i)));
+            for (j, point) in contour.points.iter_mut().enumerate() {
+                point.replace_identifier(identifier(&format!("point{}.{}", i, j)));
+            }
+        }
+
+        // The contours are reordered, and the square has a point moved.
+        let mut glyph = fixtures::ufo_glyph("a", 500.0, TRIANGLE);
+        let mut moved = SQUARE.to_vec();
+        moved[0].0 = 0.0;
+        glyph
+            .contours
+            .extend(fixtures::ufo_glyph("a", 500.0, &moved).contours);
+        adopt_identifiers(&mut glyph, &existing);
+
+        let triangle = &glyph.contours[0];
+        assert_eq!(triangle.identifier(), Some(&identifier("contour1")));
+        assert_eq!(
+            triangle.points[2].identifier(),
+            Some(&identifier("point1.2"))
+        );
+        let square = &glyph.contours[1];
+        assert_eq!(square.identifier(), None);
+        assert!(square
+            .points
+            .iter()
+            .all(|point| point.identifier().is_none()));
+    }
+
+    #[test]
+    fn convert_in_memory() {
+        let (designspace, ufos) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        let mut font =
+            convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        let bold_id = font.font_master[1].id.clone();
+        let bold = font.glyphs[0]
+            .layers
+            .iter_mut()
+            .find(|layer| layer.layer_id == bold_id)
+            .unwrap();
+        bold.width = 600.0;
+        for node in &mut bold.paths.as_mut().unwrap()[0].nodes {
+            node.pt.x += 50.0;
+        }
+
+        let (mut designspace, mut synced) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        let axes_changed = convert_to_designspace(
+            font,
+            &HashMap::new(),
+            &mut designspace,
+            &mut synced,
+            &Glyphs2DesignspaceOptions::default(),
+        )
+        .unwrap();
+        assert!(!axes_changed);
+        let regular = synced[&fixtures::ufo_filename("Regular")]
+            .get_glyph("a")
+            .unwrap();
+        assert_eq!(regular.width, 500.0);
+        let bold = synced[&fixtures::ufo_filename("Bold")]
+            .get_glyph("a")
+            .unwrap();
+        assert_eq!(bold.width, 600.0);
+        let moved: Vec<(f64, f64)> = fixtures::points(regular)[0]
+            .iter()
+            .map(|&(x, y)| (x + 50.0, y))
+            .collect();
+        assert_eq!(fixtures::points(bold), vec![moved]);
+    }
+
+    #[test]
+    fn replace_axes() {
+        let text = "<?xml version='1.0' encoding='UTF-8'?>\r\n<designspace format=\"4.1\">\r\n  <axes>\r\n    <axis tag=\"wght\" name=\"Weight\" minimum=\"400\" maximum=\"700\" default=\"400\"/>\r\n  </axes>\r\n  <sources/>\r\n</designspace>\r\n";
+        let axes = vec![
+            designspace::Axis {
+                name: "Weight".into(),
+                tag: "wght".into(),
+                default: 400.0,
+                hidden: true,
+                minimum: Some(400.0),
+                maximum: Some(700.0),
+                map: Some(vec![designspace::AxisMapping {
+                    input: 400.0,
+                    output: 80.5,
+                }]),
+                ..Default::default()
+            },
+            designspace::Axis {
+                name: "Width & Slant".into(),
+                tag: "wdth".into(),
+                default: 100.0,
+                minimum: Some(75.0),
+                maximum: Some(100.0),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            replace_designspace_axes(text, &axes).unwrap(),
+            "<?xml version='1.0' encoding='UTF-8'?>\r\n<designspace format=\"4.1\">\r\n  <axes>\r\n    <axis tag=\"wght\" name=\"Weight\" minimum=\"400\" maximum=\"700\" default=\"400\" hidden=\"1\">\r\n      <map input=\"400\" output=\"80.5\"/>\r\n    </axis>\r\n    <axis tag=\"wdth\" name=\"Width &amp; Slant\" minimum=\"75\" maximum=\"100\" default=\"100\"/>\r\n  </axes>\r\n  <sources/>\r\n</designspace>\r\n"
+        );
+
+        // Axis labels cannot be kept, so the axes are left alone.
+        let text = "<designspace>\n  <axes>\n    <axis tag=\"wght\" name=\"Weight\" default=\"400\">\n      <labelname xml:lang=\"fr\">Graisse</labelname>\n    </axis>\n  </axes>\n</designspace>\n";
+        assert!(replace_designspace_axes(text, &axes).is_err());
+    }
+
+    #[test]
+    fn invalid_anchor_name() {
+        let mut glyph = fixtures::glyphs_glyph("a", 500.0, SQUARE);
+        glyph.layers[0].anchors = Some(vec![glyphs_plist::Anchor {
+            name: "top\u{1}".into(),
+            position: kurbo::Point::new(250.0, 300.0),
+            orientation: None,
+            user_data: None,
+            glyphs3: false,
+            other_stuff: Default::default(),
+        }]);
+        let result = convert_glyphs_glyph_to_ufo_glyph(&glyph, &glyph.layers[0]);
+        assert!(matches!(result, Err(Error::Mapping(_))));
+    }
+
+    /// A directory in the system temporary directory, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let name = format!("glyphs-exchange-{}", uuid::Uuid::new_v4());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// All files below `dir`, with their contents.
+    fn read_files(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(read_files(&path));
+            } else {
+                let data = fs::read(&path).unwrap();
+                files.insert(path, data);
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn write_only_changed_files() {
+        let temp_dir = TempDir::new();
+        let dir = &temp_dir.0;
+        let designspace_path = dir.join("Test.designspace");
+        let (_, ufos) = fixtures::masters(vec![
+            fixtures::ufo_glyph("a", 500.0, SQUARE),
+            fixtures::ufo_glyph("b", 500.0, TRIANGLE),
+        ]);
+        fs::create_dir_all(dir).unwrap();
+        for (filename, ufo) in &ufos {
+            ufo.save(dir.join(filename)).unwrap();
+        }
+        let loaded: Ufos = ufos
+            .keys()
+            .map(|filename| {
+                (
+                    filename.clone(),
+                    norad::Font::load(dir.join(filename)).unwrap(),
+                )
+            })
+            .collect();
+        let before = read_files(dir);
+
+        let regular = fixtures::ufo_filename("Regular");
+        let mut ufos = loaded.clone();
+        let ufo = ufos.get_mut(&regular).unwrap();
+        ufo.default_layer_mut()
+            .insert_glyph(fixtures::ufo_glyph("a", 600.0, TRIANGLE));
+        write_changed_ufos(&designspace_path, &loaded, &ufos).unwrap();
+        let after = read_files(dir);
+        let changed: Vec<&PathBuf> = after
+            .iter()
+            .filter(|(path, data)| before.get(*path) != Some(data))
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(changed, vec![&dir.join(&regular).join("glyphs/a.glif")]);
+        assert_eq!(before.len(), after.len());
+
+        // Font info written on its own is the same as in a full save.
+        let loaded = ufos.clone();
+        let ufo = ufos.get_mut(&regular).unwrap();
+        ufo.font_info.style_name = Some("Book".into());
+        write_changed_ufos(&designspace_path, &loaded, &ufos).unwrap();
+        let fontinfo = fs::read(dir.join(&regular).join("fontinfo.plist")).unwrap();
+        let full_path = dir.join("Full.ufo");
+        ufos[&regular].save(&full_path).unwrap();
+        assert_eq!(
+            fontinfo,
+            fs::read(full_path.join("fontinfo.plist")).unwrap()
+        );
+    }
+}
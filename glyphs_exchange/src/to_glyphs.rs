@@ -1,20 +1,45 @@
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
+use log::warn;
 use maplit::hashmap;
 use norad::designspace;
 use rayon::prelude::*;
 
 use glyphs_plist;
-use glyphs_plist::{Layer, Plist};
+use glyphs_plist::{FromPlist, Layer, Plist};
 
+use crate::changes::{compare_glyphs_fonts, GlyphChange};
+use crate::error::{load_glyphs_font, load_ufo, parent_dir, Error, Result};
 use crate::location::Location;
+use crate::to_designspace::{
+    background_layer_name, BACKGROUND_IMAGE_CROP_LIB_KEY, BACKGROUND_IMAGE_LOCKED_LIB_KEY,
+    DEFAULT_BACKGROUND_LAYER_NAME, HINTS_LIB_KEY, VERTICAL_ORIGIN_LIB_KEY,
+};
+use crate::Ufos;
 
+/// Options for converting UFO sources into a Glyphs.app file.
 #[derive(Debug)]
-struct DesignspaceContext {
-    designspace: designspace::DesignSpaceDocument,
-    ufos: HashMap<String, norad::Font>,
+pub struct Ufo2GlyphsOptions {
+    /// The UFO layer to read the backgrounds of master layers from.
+    pub background_layer_name: String,
+}
+
+impl Default for Ufo2GlyphsOptions {
+    fn default() -> Self {
+        Self {
+            background_layer_name: DEFAULT_BACKGROUND_LAYER_NAME.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DesignspaceContext<'a> {
+    designspace: &'a designspace::DesignSpaceDocument,
+    ufos: &'a Ufos,
     ids: HashMap<String, String>,
+    // The names of sparse sources, which become brace layers.
+    sparse_sources: HashSet<String>,
 }
 
 #[derive(Debug)]
@@ -22,6 +47,7 @@ struct FontProperties {
     disables_automatic_alignment: bool,
     family_name: String,
     glyph_order: Vec<String>,
+    properties: Vec<glyphs_plist::Property>,
     units_per_em: i64,
     version_major: i64,
     version_minor: i64,
@@ -33,46 +59,26 @@ enum LayerId {
     AssociatedWithMaster {
         associated_master_id: String,
         layer_id: String,
-        ufo_layer_name: String,
+        // None for the default layer of a sparse UFO.
+        ufo_layer_name: Option<String>,
         glyphs_layer_name: String,
     },
 }
 
-impl DesignspaceContext {
-    fn from_path(designspace_path: &Path) -> Self {
-        let designspace = designspace::DesignSpaceDocument::load(designspace_path)
-            .expect("Cannot load Designspace.");
-
-        // Check that all sources have unique names, otherwise panic.
-        let unique_sources: HashSet<_> = designspace
-            .sources
-            .iter()
-            .map(|source| source.name.as_str())
-            .collect();
-        if unique_sources.len() != designspace.sources.len() {
-            panic!("Designspace sources must have unique names.");
-        }
-
-        // Check that we have at most six axes (Glyphs.app v2.x limitation).
-        if designspace.axes.len() > 6 {
-            panic!("Designspace must have at most six axes.");
+impl<'a> DesignspaceContext<'a> {
+    /// Check the Designspace and its UFOs, failing with every problem found
+    /// by the preflight checks.
+    fn new(designspace: &'a designspace::DesignSpaceDocument, ufos: &'a Ufos) -> Result<Self> {
+        let mut problems = preflight(designspace);
+        for source in &designspace.sources {
+            if !ufos.contains_key(&source.filename) {
+                problems.push(format!(
+                    "Source {} uses UFO {}, which is not loaded.",
+                    source.name, source.filename
+                ));
+            }
         }
-
-        let unique_filenames: HashSet<String> = designspace
-            .sources
-            .iter()
-            .map(|source| source.filename.to_string())
-            .collect();
-        let designspace_dir = designspace_path.parent().unwrap();
-        let ufos: HashMap<String, norad::Font> = unique_filenames
-            .into_par_iter()
-            .map(|filename| {
-                (
-                    filename.clone(),
-                    norad::Font::load(designspace_dir.join(filename)).expect("Could not load UFO"),
-                )
-            })
-            .collect();
+        problems.extend(check_layers(designspace, ufos));
 
         let ids = designspace
             .sources
@@ -85,59 +91,170 @@ impl DesignspaceContext {
             })
             .collect();
 
-        Self {
+        let mut context = Self {
             designspace,
             ufos,
             ids,
+            sparse_sources: HashSet::new(),
+        };
+        if problems.is_empty() && context.find_default_source().is_none() {
+            problems.push("No source is at the default location of all axes.".into());
+        }
+        if !problems.is_empty() {
+            return Err(Error::Validation(problems));
         }
+        context.sparse_sources = context.find_sparse_sources()?;
+        Ok(context)
+    }
+
+    /// The UFO of `source`.
+    fn ufo(&self, source: &designspace::Source) -> Result<&norad::Font> {
+        self.ufos.get(&source.filename).ok_or_else(|| {
+            Error::Validation(vec![format!(
+                "Source {} uses UFO {}, which is not loaded.",
+                source.name, source.filename
+            )])
+        })
     }
 
-    fn id_for_source_name(&self, source: &designspace::Source) -> LayerId {
-        if source.layer.is_none() {
-            LayerId::Master(self.ids[&source.name].clone())
+    /// Find the sources that are not full masters: those pointing to a UFO
+    /// layer, and Designspace 5 style sparse UFOs of their own, which have
+    /// no master font info, i.e. no units per em.
+    fn find_sparse_sources(&self) -> Result<HashSet<String>> {
+        let default_source = self.default_source()?;
+        let mut sparse_sources = HashSet::new();
+        for source in &self.designspace.sources {
+            if source.name != default_source.name
+                && (source.layer.is_some() || self.ufo(source)?.font_info.units_per_em.is_none())
+            {
+                sparse_sources.insert(source.name.clone());
+            }
+        }
+        Ok(sparse_sources)
+    }
+
+    fn is_master(&self, source: &designspace::Source) -> bool {
+        !self.sparse_sources.contains(&source.name)
+    }
+
+    fn id_for_source_name(&self, source: &designspace::Source) -> Result<LayerId> {
+        if self.is_master(source) {
+            Ok(LayerId::Master(self.ids[&source.name].clone()))
         } else {
-            let parent_source = self
-                .designspace
-                .sources
-                .iter()
-                .find(|parent_source| parent_source.filename == source.filename)
-                .expect("Parent source not found in Designspace.");
-            LayerId::AssociatedWithMaster {
+            let parent_source = self.parent_source(source)?;
+            Ok(LayerId::AssociatedWithMaster {
                 associated_master_id: self.ids[&parent_source.name].clone(),
                 layer_id: self.ids[&source.name].clone(),
-                ufo_layer_name: source.layer.clone().unwrap(),
-                glyphs_layer_name: Location::from_dimension(&source.location).to_string(),
+                ufo_layer_name: source.layer.clone(),
+                glyphs_layer_name: Location::from_dimension(&source.location)?.to_string(),
+            })
+        }
+    }
+
+    /// The master a sparse source becomes a brace layer of: the master of
+    /// the same UFO, if any, or the nearest one in the design space.
+    fn parent_source(&self, source: &designspace::Source) -> Result<&designspace::Source> {
+        let masters = self
+            .designspace
+            .sources
+            .iter()
+            .filter(|master| self.is_master(master));
+        if let Some(master) = masters
+            .clone()
+            .find(|master| master.filename == source.filename)
+        {
+            return Ok(master);
+        }
+        let location = Location::from_dimension(&source.location)?;
+        let distances = masters
+            .map(|master| {
+                let distance = location.distance(&Location::from_dimension(&master.location)?);
+                Ok((master, distance))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        distances
+            .into_iter()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(master, _)| master)
+            .ok_or_else(|| {
+                Error::Validation(vec![format!(
+                    "Sparse source {} has no full master to become a layer of.",
+                    source.name
+                )])
+            })
+    }
+
+    /// "Virtual Master" custom parameters for the locations of sparse
+    /// sources outside the design space of the masters, without which
+    /// Glyphs.app ignores their brace layers.
+    fn virtual_masters(&self) -> Result<Vec<Plist>> {
+        let (masters, sparse_sources): (Vec<_>, Vec<_>) = self
+            .designspace
+            .sources
+            .iter()
+            .partition(|source| self.is_master(source));
+        let masters = masters
+            .iter()
+            .map(|source| Location::from_dimension(&source.location))
+            .collect::<Result<Vec<_>>>()?;
+        let mut seen = HashSet::new();
+        let mut outside = Vec::new();
+        for source in sparse_sources {
+            let location = Location::from_dimension(&source.location)?;
+            if !location.is_within(&masters) && seen.insert(location.to_string()) {
+                outside.push(source);
             }
         }
+        Ok(outside
+            .into_iter()
+            .map(|source| {
+                let value: Vec<Plist> = source
+                    .location
+                    .iter()
+                    .map(|dim| {
+                        hashmap! {
+                            "Axis".into() => dim.name.clone().into(),
+                            "Location".into() => (dim.xvalue.unwrap_or(0.0) as f64).into(),
+                        }
+                        .into()
+                    })
+                    .collect();
+                hashmap! {
+                    "name".into() => String::from("Virtual Master").into(),
+                    "value".into() => value.into(),
+                }
+                .into()
+            })
+            .collect())
     }
 
-    fn axis_by_name(&self, name: &str) -> &designspace::Axis {
+    fn axis_by_name(&self, name: &str) -> Result<&designspace::Axis> {
         self.designspace
             .axes
             .iter()
             .find(|axis| axis.name == name)
-            .expect("Cannot find axis by name")
+            .ok_or_else(|| Error::Mapping(format!("Cannot find axis {} by name.", name)))
     }
 
     // TODO: Fix reliance on the order of dimensions in the location and axes.
-    fn axis_location(&self, source: &designspace::Source) -> Plist {
-        source
+    fn axis_location(&self, source: &designspace::Source) -> Result<Plist> {
+        let locations = source
             .location
             .iter()
             .map(|dim| {
-                let axis = self.axis_by_name(&dim.name);
-                let value = Self::map_axis_value_backwards(axis, dim.xvalue.unwrap_or(0.0));
-                Plist::Dictionary(
+                let axis = self.axis_by_name(&dim.name)?;
+                let value = Self::map_axis_value_backwards(axis, dim.xvalue.unwrap_or(0.0))?;
+                Ok(Plist::Dictionary(
                     vec![
                         ("Axis".to_string(), Plist::String(axis.name.clone())),
                         ("Location".to_string(), Plist::Integer(value.round() as i64)),
                     ]
                     .into_iter()
                     .collect(),
-                )
+                ))
             })
-            .collect::<Vec<_>>()
-            .into()
+            .collect::<Result<Vec<_>>>()?;
+        Ok(locations.into())
     }
 
     fn global_axes(&self) -> Plist {
@@ -145,78 +262,106 @@ impl DesignspaceContext {
             .axes
             .iter()
             .map(|axis| {
-                Plist::Dictionary(
-                    vec![
-                        ("Name".to_string(), Plist::String(axis.name.clone())),
-                        ("Tag".to_string(), Plist::String(axis.tag.clone())),
-                    ]
-                    .into_iter()
-                    .collect(),
-                )
+                let mut entries = vec![
+                    ("Name".to_string(), Plist::String(axis.name.clone())),
+                    ("Tag".to_string(), Plist::String(axis.tag.clone())),
+                ];
+                if axis.hidden {
+                    entries.push(("Hidden".to_string(), Plist::Integer(1)));
+                }
+                Plist::Dictionary(entries.into_iter().collect())
             })
             .collect::<Vec<_>>()
             .into()
     }
 
-    fn map_axis_value_backwards(axis: &designspace::Axis, value: f32) -> f32 {
+    /// The "Axis Mappings" custom parameter value, mapping user to design
+    /// locations by axis tag, if any axis has a mapping.
+    fn axis_mappings(&self) -> Option<Plist> {
+        let mappings: HashMap<String, Plist> = self
+            .designspace
+            .axes
+            .iter()
+            .filter_map(|axis| {
+                let mapping: HashMap<String, Plist> = axis
+                    .map
+                    .as_ref()?
+                    .iter()
+                    .map(|map| (map.input.to_string(), (map.output as f64).into()))
+                    .collect();
+                Some((axis.tag.clone(), mapping.into()))
+            })
+            .collect();
+        (!mappings.is_empty()).then(|| mappings.into())
+    }
+
+    fn map_axis_value_backwards(axis: &designspace::Axis, value: f32) -> Result<f32> {
         if let Some(mapping) = &axis.map {
             mapping
                 .iter()
                 .find(|map| map.output == value)
                 .map(|map| map.input)
                 .ok_or_else(|| {
-                    format!(
+                    Error::Mapping(format!(
                         "Could not find exact axis design to user mapping; axis {}, value {}",
                         &axis.name, value
-                    )
+                    ))
                 })
-                .unwrap()
         } else {
-            value
+            Ok(value)
         }
     }
 
-    fn map_axis_value_forwards(axis: &designspace::Axis, value: f32) -> f32 {
+    fn map_axis_value_forwards(axis: &designspace::Axis, value: f32) -> Result<f32> {
         if let Some(mapping) = &axis.map {
             mapping
                 .iter()
                 .find(|map| map.input == value)
                 .map(|map| map.output)
                 .ok_or_else(|| {
-                    format!(
+                    Error::Mapping(format!(
                         "Could not find exact axis design to user mapping; axis {}, value {}",
                         &axis.name, value
-                    )
+                    ))
                 })
-                .unwrap()
         } else {
-            value
+            Ok(value)
         }
     }
 
-    fn default_source(&self) -> &designspace::Source {
-        let default_location: Vec<designspace::Dimension> = self
+    fn default_source(&self) -> Result<&designspace::Source> {
+        self.find_default_source().ok_or_else(|| {
+            Error::Validation(vec![
+                "No source is at the default location of all axes.".into()
+            ])
+        })
+    }
+
+    fn find_default_source(&self) -> Option<&designspace::Source> {
+        let default_location = self
             .designspace
             .axes
             .iter()
-            .map(|a| designspace::Dimension {
-                name: a.name.clone(),
-                xvalue: Some(Self::map_axis_value_forwards(a, a.default)),
-                ..Default::default()
+            .map(|a| {
+                Ok(designspace::Dimension {
+                    name: a.name.clone(),
+                    xvalue: Some(Self::map_axis_value_forwards(a, a.default)?),
+                    ..Default::default()
+                })
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()
+            .ok()?;
         self.designspace
             .sources
             .iter()
             .find(|source| source.location == default_location)
-            .expect("Could not find default source")
     }
 }
 
 impl FontProperties {
-    fn from_context(context: &DesignspaceContext) -> Self {
-        let default_source = context.default_source();
-        let default_ufo = context.ufos.get(&default_source.filename).unwrap();
+    fn from_context(context: &DesignspaceContext) -> Result<Self> {
+        let default_source = context.default_source()?;
+        let default_ufo = context.ufo(default_source)?;
 
         let family_name: String = default_ufo
             .font_info
@@ -254,10 +399,18 @@ impl FontProperties {
             if let Some(glyph_order) = default_ufo.lib.get("public.glyphOrder") {
                 let mut glyph_order: Vec<String> = glyph_order
                     .as_array()
-                    .expect("glyphOrder must be list of strings.")
-                    .iter()
-                    .map(|v| v.as_string().unwrap().to_string())
-                    .collect();
+                    .and_then(|names| {
+                        names
+                            .iter()
+                            .map(|v| v.as_string().map(str::to_string))
+                            .collect()
+                    })
+                    .ok_or_else(|| {
+                        Error::Mapping(format!(
+                            "The public.glyphOrder of {} must be a list of strings.",
+                            default_source.filename
+                        ))
+                    })?;
 
                 let glyph_order_set = HashSet::from_iter(&glyph_order);
                 let mut leftovers: Vec<String> = all_glyphs_set
@@ -276,34 +429,114 @@ impl FontProperties {
                 all_glyphs
             };
 
-        Self {
+        let properties = glyphs_plist::Property::from_font_info(&default_ufo.font_info);
+
+        Ok(Self {
             disables_automatic_alignment,
             family_name,
             glyph_order,
+            properties,
             units_per_em,
             version_major,
             version_minor,
+        })
+    }
+}
+
+/// Load a Designspace and its UFOs, failing with every problem found if any
+/// UFO cannot be loaded.
+pub fn load_designspace(
+    designspace_path: &Path,
+) -> Result<(designspace::DesignSpaceDocument, Ufos)> {
+    let designspace = designspace::DesignSpaceDocument::load(designspace_path)
+        .map_err(|e| Error::designspace(designspace_path, format!("{:?}", e)))?;
+
+    let unique_filenames: HashSet<String> = designspace
+        .sources
+        .iter()
+        .map(|source| source.filename.to_string())
+        .collect();
+    let designspace_dir = parent_dir(designspace_path)?;
+    let loaded: Vec<(String, Result<norad::Font>)> = unique_filenames
+        .into_par_iter()
+        .map(|filename| {
+            let ufo = load_ufo(&designspace_dir.join(&filename));
+            (filename, ufo)
+        })
+        .collect();
+    let mut ufos = HashMap::new();
+    let mut problems = Vec::new();
+    for (filename, ufo) in loaded {
+        match ufo {
+            Ok(ufo) => {
+                ufos.insert(filename, ufo);
+            }
+            Err(e) => problems.push(e.to_string()),
         }
     }
+    if !problems.is_empty() {
+        // Report what else is wrong, as far as it can be told.
+        problems.extend(preflight(&designspace));
+        problems.extend(check_layers(&designspace, &ufos));
+        return Err(Error::Validation(problems));
+    }
+    Ok((designspace, ufos))
 }
 
-pub fn command_to_glyphs(designspace_path: &Path) -> glyphs_plist::Font {
-    let context = DesignspaceContext::from_path(designspace_path);
+/// Convert a Designspace and its UFOs into a Glyphs.app file.
+pub fn command_to_glyphs(
+    designspace_path: &Path,
+    options: &Ufo2GlyphsOptions,
+) -> Result<glyphs_plist::Font> {
+    let (designspace, ufos) = load_designspace(designspace_path)?;
+    convert_to_glyphs(&designspace, &ufos, options)
+}
 
-    let font_properties = FontProperties::from_context(&context);
+/// Convert a Designspace and its UFOs into a Glyphs.app file without saving
+/// it, and report the glyphs that would change compared to the one at
+/// `glyphs_path`, if any.
+pub fn command_to_glyphs_changes(
+    designspace_path: &Path,
+    glyphs_path: &Path,
+    options: &Ufo2GlyphsOptions,
+) -> Result<Vec<GlyphChange>> {
+    let font = command_to_glyphs(designspace_path, options)?;
+    let old = if glyphs_path.exists() {
+        Some(load_glyphs_font(glyphs_path)?)
+    } else {
+        None
+    };
+    let source = glyphs_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    compare_glyphs_fonts(&source, old.as_ref(), &font)
+}
+
+/// Convert a Designspace and its UFOs, by source file name, into a
+/// Glyphs.app file, failing with every problem found by the preflight
+/// checks.
+pub fn convert_to_glyphs(
+    designspace: &designspace::DesignSpaceDocument,
+    ufos: &Ufos,
+    options: &Ufo2GlyphsOptions,
+) -> Result<glyphs_plist::Font> {
+    let context = DesignspaceContext::new(designspace, ufos)?;
+
+    let font_properties = FontProperties::from_context(&context)?;
     let font_master: Vec<glyphs_plist::FontMaster> = context
         .designspace
         .sources
         .iter()
-        .filter(|source| source.layer.is_none())
+        .filter(|source| context.is_master(source))
         .map(|source| master_from(&context, source))
-        .collect();
+        .collect::<Result<_>>()?;
     let instances: Vec<glyphs_plist::Instance> = context
         .designspace
         .instances
         .iter()
         .map(instance_from)
-        .collect();
+        .collect::<Result<_>>()?;
 
     // First, convert the glyphs...
     let mut glyphs: Vec<HashMap<norad::Name, glyphs_plist::Layer>> = context
@@ -311,36 +544,83 @@ pub fn command_to_glyphs(designspace_path: &Path) -> glyphs_plist::Font {
         .sources
         .iter()
         .map(|source| {
-            let layer_id = context.id_for_source_name(source);
-            let font = &context.ufos[&source.filename];
-            let ufo_layer = match &layer_id {
-                LayerId::Master(_) => font.default_layer(),
-                LayerId::AssociatedWithMaster { ufo_layer_name, .. } => {
-                    font.layers.get(ufo_layer_name).unwrap_or_else(|| {
-                        panic!(
+            let layer_id = context.id_for_source_name(source)?;
+            let font = context.ufo(source)?;
+            let (ufo_layer, background_name) = match &layer_id {
+                LayerId::Master(_) => (
+                    font.default_layer(),
+                    background_layer_name(None, &options.background_layer_name),
+                ),
+                LayerId::AssociatedWithMaster {
+                    ufo_layer_name: None,
+                    ..
+                } => (
+                    font.default_layer(),
+                    background_layer_name(None, &options.background_layer_name),
+                ),
+                LayerId::AssociatedWithMaster {
+                    ufo_layer_name: Some(ufo_layer_name),
+                    ..
+                } => (
+                    font.layers.get(ufo_layer_name).ok_or_else(|| {
+                        Error::Validation(vec![format!(
                             "Cannot find layer {} in {}.",
                             ufo_layer_name, &source.filename
-                        )
-                    })
-                }
+                        )])
+                    })?,
+                    background_layer_name(Some(ufo_layer_name), &options.background_layer_name),
+                ),
             };
-            (layer_id, ufo_layer)
+            let background_layer = font.layers.get(&background_name);
+            Ok((layer_id, ufo_layer, background_layer, &source.filename))
         })
         // NOTE: Running this loop in parallel is not faster, or I'm holding
         // rayon wrong...
-        .map(|(layer_id, ufo_layer)| {
+        .map(|layer| {
+            let (layer_id, ufo_layer, background_layer, ufo_filename) = layer?;
+            // Mirrors glyphs_plist::Font::default_vertical_origin.
+            let font_info = &context.ufos[ufo_filename].font_info;
+            let default_vertical_origin = font_info
+                .open_type_os2_typo_ascender
+                .map(f64::from)
+                .or(font_info.ascender);
             ufo_layer
                 .iter()
-                .map(|glyph| (glyph.name().clone(), layer_from(&layer_id, glyph)))
+                .map(|glyph| {
+                    let mut layer = layer_from(&layer_id, glyph)?;
+                    layer.background_image = glyph
+                        .image
+                        .as_ref()
+                        .map(|image| background_image_from(image, glyph, ufo_filename))
+                        .transpose()?;
+                    if let Some(origin) = glyph.lib.get(VERTICAL_ORIGIN_LIB_KEY) {
+                        let origin = origin
+                            .as_real()
+                            .or_else(|| origin.as_signed_integer().map(|v| v as f64));
+                        match default_vertical_origin {
+                            Some(default) => layer.set_vertical_origin(origin, default),
+                            None => warn!(
+                                "Dropping vertical origin of glyph {}: no ascender to store it against",
+                                glyph.name()
+                            ),
+                        }
+                    }
+                    if let Some(background) =
+                        background_layer.and_then(|l| l.get_glyph(glyph.name()))
+                    {
+                        layer.set_background_layer(layer_from(&layer_id, background)?);
+                    }
+                    Ok((glyph.name().clone(), layer))
+                })
                 .collect()
         })
-        .collect();
+        .collect::<Result<_>>()?;
 
     // ... and then sort them like the glyphOrder, or Glyphs.app will display
     // them jumbled.
     // TODO: Reuse default_source from FontProperties? Make part of context?
-    let default_source = context.default_source();
-    let default_ufo = context.ufos.get(&default_source.filename).unwrap();
+    let default_source = context.default_source()?;
+    let default_ufo = context.ufo(default_source)?;
     let default_ufo_layer = default_ufo.default_layer();
     let mut seen_glyphs = HashSet::new();
     let glyphs: Vec<glyphs_plist::Glyph> = font_properties
@@ -364,80 +644,89 @@ pub fn command_to_glyphs(designspace_path: &Path) -> glyphs_plist::Font {
         .iter()
         .map(|n| n.to_string().into())
         .collect();
-    let other_stuff: HashMap<String, Plist> = hashmap! {
-        ".appVersion".into() => String::from("1361").into(),
-        "customParameters".into() => vec![
+    let mut custom_parameters: Vec<Plist> = vec![
+        hashmap! {
+            "name".into() => String::from("Axes").into(),
+            "value".into() => context.global_axes(),
+        }
+        .into(),
+        hashmap! {
+            "name".into() => String::from("glyphOrder").into(),
+            "value".into() => glyph_order_plist.into(),
+        }
+        .into(),
+    ];
+    if let Some(axis_mappings) = context.axis_mappings() {
+        custom_parameters.push(
             hashmap! {
-                "name".into() => String::from("Axes").into(),
-                "value".into() => context.global_axes(),
-            }.into(),
+                "name".into() => String::from("Axis Mappings").into(),
+                "value".into() => axis_mappings,
+            }
+            .into(),
+        );
+    }
+    if let Some(stylename) = &default_source.stylename {
+        custom_parameters.push(
             hashmap! {
-                "name".into() => String::from("glyphOrder").into(),
-                "value".into() => glyph_order_plist.into(),
-            }.into(),
-        ].into(),
+                "name".into() => String::from("Variable Font Origin").into(),
+                "value".into() => stylename.clone().into(),
+            }
+            .into(),
+        );
+    }
+    custom_parameters.extend(context.virtual_masters()?);
+    let other_stuff: HashMap<String, Plist> = hashmap! {
+        ".appVersion".into() => String::from("1361").into(),
+        "customParameters".into() => custom_parameters.into(),
     };
 
-    glyphs_plist::Font {
+    let mut font = glyphs_plist::Font {
+        axes: None,
         disables_automatic_alignment: Some(font_properties.disables_automatic_alignment),
         family_name: font_properties.family_name,
         font_master,
         glyphs,
         instances: Some(instances),
+        metrics: None,
         other_stuff,
+        properties: None,
+        stems: None,
         units_per_em: font_properties.units_per_em,
         version_major: font_properties.version_major,
         version_minor: font_properties.version_minor,
-    }
+    };
+    // The output is a Glyphs 2 file, which has no `properties` key.
+    font.set_glyphs2_names(&font_properties.properties);
+    Ok(font)
 }
 
 fn master_from(
     context: &DesignspaceContext,
     source: &designspace::Source,
-) -> glyphs_plist::FontMaster {
-    let layer_id = context.id_for_source_name(source);
-    let font = &context.ufos[&source.filename];
+) -> Result<glyphs_plist::FontMaster> {
+    let layer_id = context.id_for_source_name(source)?;
+    let font = context.ufo(source)?;
 
     let LayerId::Master(id) = &layer_id else {
-        panic!("Master does not seem to be a master?!")
+        return Err(Error::Mapping(format!(
+            "Source {} is not a master.",
+            source.name
+        )));
     };
 
-    let location = Location::from_dimension(&source.location);
+    let location = Location::from_dimension(&source.location)?;
     let (weight_value, width_value, custom_value, custom_value1, custom_value2, custom_value3) =
         location.as_tuple();
 
-    let ascender = font
-        .font_info
-        .ascender
-        .map(|v| v.round() as i64)
-        .unwrap_or(800);
-    let cap_height = font
-        .font_info
-        .cap_height
-        .map(|v| v.round() as i64)
-        .unwrap_or(700);
-    let descender = font
-        .font_info
-        .descender
-        .map(|v| v.round() as i64)
-        .unwrap_or(-200);
-    let x_height = font
-        .font_info
-        .x_height
-        .map(|v| v.round() as i64)
-        .unwrap_or(500);
-    let italic_angle = font.font_info.italic_angle.map(|v| -v);
-
-    let source_name = source
-        .stylename
-        .as_ref()
-        .expect("Source must have a stylename");
+    let source_name = source.stylename.as_ref().ok_or_else(|| {
+        Error::Validation(vec![format!("Source {} has no style name.", source.name)])
+    })?;
 
     let other_stuff = hashmap! {
         "customParameters".into() => vec![
             hashmap! {
                 "name".into() => String::from("Axis Location").into(),
-                "value".into() => context.axis_location(source),
+                "value".into() => context.axis_location(source)?,
             }.into(),
             hashmap! {
                 "name".into() => String::from("Master Name").into(),
@@ -446,26 +735,200 @@ fn master_from(
         ].into(),
     };
 
-    glyphs_plist::FontMaster {
-        ascender: Some(ascender),
-        cap_height: Some(cap_height),
+    let mut master = glyphs_plist::FontMaster {
+        alignment_zones: None,
+        ascender: None,
+        axes_values: None,
+        cap_height: None,
         custom_value,
         custom_value1,
         custom_value2,
         custom_value3,
-        descender: Some(descender),
+        descender: None,
+        horizontal_stems: None,
         id: id.clone(),
-        italic_angle,
+        italic_angle: None,
+        metric_values: None,
         other_stuff,
+        stem_values: None,
+        vertical_stems: None,
         weight_value: Some(weight_value),
         width_value,
-        x_height: Some(x_height),
+        x_height: None,
+    };
+    // Only the metrics the UFO has, Glyphs.app fills in the others.
+    master.set_master_metrics(&glyphs_plist::MasterMetrics::from(&font.font_info));
+    master.set_postscript_hinting(&glyphs_plist::PostscriptHinting::from(&font.font_info));
+    master.set_vertical_metrics(&glyphs_plist::VerticalMetrics::from(&font.font_info));
+    Ok(master)
+}
+
+/// Check the Designspace for everything that would stop or spoil the
+/// conversion, without loading the UFOs.
+fn preflight(designspace: &designspace::DesignSpaceDocument) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut source_names = HashSet::new();
+    for source in &designspace.sources {
+        if !source_names.insert(source.name.as_str()) {
+            problems.push(format!(
+                "Source name {} is used more than once.",
+                source.name
+            ));
+        }
+    }
+
+    // Glyphs.app v2.x supports at most six axes.
+    if designspace.axes.len() > 6 {
+        problems.push(format!(
+            "Designspace has {} axes, at most six are supported.",
+            designspace.axes.len()
+        ));
+    }
+    let mut tags = HashSet::new();
+    for axis in &designspace.axes {
+        if !tags.insert(axis.tag.as_str()) {
+            problems.push(format!("Axis tag {} is used more than once.", axis.tag));
+        }
+
+        let mut mapping: Vec<_> = axis.map.iter().flatten().collect();
+        mapping.sort_by(|a, b| a.input.total_cmp(&b.input));
+        for pair in mapping.windows(2) {
+            if pair[0].input == pair[1].input {
+                problems.push(format!(
+                    "Axis {} maps user location {} more than once.",
+                    axis.name, pair[0].input
+                ));
+            } else if pair[1].output < pair[0].output {
+                problems.push(format!(
+                    "Axis {} has a mapping that is not monotonic at user location {}.",
+                    axis.name, pair[1].input
+                ));
+            }
+        }
+        if !mapping.is_empty() && !mapping.iter().any(|map| map.input == axis.default) {
+            problems.push(format!(
+                "Axis {} does not map its default {}.",
+                axis.name, axis.default
+            ));
+        }
+        let minimum = axis.minimum.unwrap_or(axis.default);
+        let maximum = axis.maximum.unwrap_or(axis.default);
+        if !(minimum..=maximum).contains(&axis.default) {
+            problems.push(format!(
+                "Axis {} has default {} outside of its range {} to {}.",
+                axis.name, axis.default, minimum, maximum
+            ));
+        }
+    }
+
+    let check_location =
+        |problems: &mut Vec<String>, what: String, location: &[designspace::Dimension]| {
+            for dimension in location {
+                let Some(axis) = designspace.axes.iter().find(|a| a.name == dimension.name) else {
+                    problems.push(format!("{} is on unknown axis {}.", what, dimension.name));
+                    continue;
+                };
+                let value = dimension.xvalue.unwrap_or(0.0);
+                if let Some((minimum, maximum)) = design_range(axis) {
+                    if !(minimum..=maximum).contains(&value) {
+                        problems.push(format!(
+                            "{} is at {} on axis {}, outside of its range {} to {}.",
+                            what, value, axis.name, minimum, maximum
+                        ));
+                    }
+                }
+            }
+        };
+    for source in &designspace.sources {
+        check_location(
+            &mut problems,
+            format!("Source {}", source.name),
+            &source.location,
+        );
+    }
+    for instance in &designspace.instances {
+        let name = if instance.name.is_empty() {
+            instance.stylename.clone().unwrap_or_default()
+        } else {
+            instance.name.clone()
+        };
+        check_location(
+            &mut problems,
+            format!("Instance {}", name),
+            &instance.location,
+        );
+        if let Some(style) = &instance.stylemapstylename {
+            if !matches!(
+                style.as_str(),
+                "regular" | "bold" | "italic" | "bold italic"
+            ) {
+                problems.push(format!(
+                    "Instance {} has unknown style map style name {}.",
+                    name, style
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+/// Check that the UFO layers the sources use exist, in the UFOs there are.
+fn check_layers(designspace: &designspace::DesignSpaceDocument, ufos: &Ufos) -> Vec<String> {
+    designspace
+        .sources
+        .iter()
+        .filter_map(|source| {
+            let layer = source.layer.as_ref()?;
+            let ufo = ufos.get(&source.filename)?;
+            ufo.layers.get(layer).is_none().then(|| {
+                format!(
+                    "Source {} uses layer {}, which does not exist in {}.",
+                    source.name, layer, source.filename
+                )
+            })
+        })
+        .collect()
+}
+
+/// The range of an axis in design coordinates, if it has one.
+fn design_range(axis: &designspace::Axis) -> Option<(f32, f32)> {
+    match &axis.map {
+        Some(mapping) if !mapping.is_empty() => {
+            let outputs = mapping.iter().map(|map| map.output);
+            Some((
+                outputs.clone().fold(f32::INFINITY, f32::min),
+                outputs.fold(f32::NEG_INFINITY, f32::max),
+            ))
+        }
+        _ => Some((axis.minimum?, axis.maximum?)),
+    }
+}
+
+/// Run the preflight checks on a Designspace and its UFOs, and return all
+/// problems found.
+pub fn command_preflight(designspace_path: &Path) -> Result<Vec<String>> {
+    validation_problems(
+        load_designspace(designspace_path).and_then(|(designspace, ufos)| {
+            DesignspaceContext::new(&designspace, &ufos)?;
+            Ok(())
+        }),
+    )
+}
+
+/// The problems of a failed validation. Other errors are passed on.
+fn validation_problems(checked: Result<()>) -> Result<Vec<String>> {
+    match checked {
+        Ok(()) => Ok(Vec::new()),
+        Err(Error::Validation(problems)) => Ok(problems),
+        Err(e) => Err(e),
     }
 }
 
-fn instance_from(instance: &designspace::Instance) -> glyphs_plist::Instance {
+fn instance_from(instance: &designspace::Instance) -> Result<glyphs_plist::Instance> {
     let name = instance.stylename.clone().unwrap_or_default();
-    let location = Location::from_dimension(&instance.location);
+    let location = Location::from_dimension(&instance.location)?;
     let (
         interpolation_weight,
         interpolation_width,
@@ -482,7 +945,12 @@ fn instance_from(instance: &designspace::Instance) -> glyphs_plist::Instance {
             "bold" => (true, false),
             "italic" => (false, true),
             "bold italic" => (true, true),
-            _ => panic!("Unrecognized style map style name"),
+            _ => {
+                return Err(Error::Validation(vec![format!(
+                    "Instance {} has unknown style map style name {}.",
+                    instance.name, style
+                )]))
+            }
         },
         None => (false, false),
     };
@@ -490,7 +958,7 @@ fn instance_from(instance: &designspace::Instance) -> glyphs_plist::Instance {
     let link_style = instance.stylemapfamilyname.clone();
     let other_stuff: HashMap<String, Plist> = HashMap::new();
 
-    glyphs_plist::Instance {
+    Ok(glyphs_plist::Instance {
         name,
         interpolation_weight: Some(interpolation_weight),
         interpolation_width,
@@ -498,14 +966,17 @@ fn instance_from(instance: &designspace::Instance) -> glyphs_plist::Instance {
         interpolation_custom1,
         interpolation_custom2,
         interpolation_custom3,
+        axes_values: None,
         is_bold: Some(is_bold),
         is_italic: Some(is_italic),
         link_style,
+        // norad's Designspace model has no localized instance names to map.
+        properties: None,
         other_stuff,
-    }
+    })
 }
 
-fn layer_from(layer_id: &LayerId, glyph: &norad::Glyph) -> Layer {
+fn layer_from(layer_id: &LayerId, glyph: &norad::Glyph) -> Result<Layer> {
     let (associated_master_id, layer_id, layer_name) = match layer_id {
         LayerId::Master(id) => (None, id.clone(), None),
         LayerId::AssociatedWithMaster {
@@ -535,11 +1006,37 @@ fn layer_from(layer_id: &LayerId, glyph: &norad::Glyph) -> Layer {
     let anchors: Vec<glyphs_plist::Anchor> = glyph
         .anchors
         .iter()
-        .filter(|anchor| anchor.name.is_some())
-        .map(|anchor| anchor.into())
+        .filter_map(|anchor| match anchor.try_into() {
+            Ok(anchor) => Some(anchor),
+            Err(e) => {
+                warn!("Skipping anchor of glyph {}: {}", glyph.name(), e);
+                None
+            }
+        })
         .collect();
 
-    Layer {
+    let hints = match glyph.lib.get(HINTS_LIB_KEY) {
+        Some(hints) => {
+            let hints: Plist = hints.try_into().map_err(|e| {
+                Error::Mapping(format!(
+                    "Cannot convert hints of glyph {}: {:?}",
+                    glyph.name(),
+                    e
+                ))
+            })?;
+            let hints = FromPlist::from_plist(hints).map_err(|e| {
+                Error::Mapping(format!(
+                    "Cannot convert hints of glyph {}: {}",
+                    glyph.name(),
+                    e
+                ))
+            })?;
+            Some(hints)
+        }
+        None => None,
+    };
+
+    Ok(Layer {
         name: layer_name,
         associated_master_id,
         layer_id,
@@ -556,8 +1053,51 @@ fn layer_from(layer_id: &LayerId, glyph: &norad::Glyph) -> Layer {
             None
         },
         guide_lines: None,
+        part_selection: None,
+        hints,
+        background: None,
+        background_image: None,
+        vert_width: (glyph.height != 0.0).then_some(glyph.height),
+        vert_origin: None,
         other_stuff: Default::default(),
-    }
+    })
+}
+
+/// Convert a UFO glyph image into a Glyphs.app background image. The image
+/// path points into the UFO's images directory relative to the Designspace,
+/// so the Glyphs file should be saved next to it. Only the alpha of the image
+/// colour is kept, as the opacity.
+fn background_image_from(
+    image: &norad::Image,
+    glyph: &norad::Glyph,
+    ufo_filename: &str,
+) -> Result<glyphs_plist::BackgroundImage> {
+    let transform: kurbo::Affine = image.transform.into();
+    let crop = match glyph.lib.get(BACKGROUND_IMAGE_CROP_LIB_KEY) {
+        Some(crop) => Some(crop.try_into().map_err(|e| {
+            Error::Mapping(format!(
+                "Cannot convert background image crop of glyph {} in {}: {:?}",
+                glyph.name(),
+                ufo_filename,
+                e
+            ))
+        })?),
+        None => None,
+    };
+    Ok(glyphs_plist::BackgroundImage {
+        image_path: format!("{}/images/{}", ufo_filename, image.file_name().display()),
+        transform: (transform != kurbo::Affine::IDENTITY).then_some(transform),
+        crop,
+        locked: glyph
+            .lib
+            .get(BACKGROUND_IMAGE_LOCKED_LIB_KEY)
+            .and_then(|locked| locked.as_boolean()),
+        alpha: image
+            .color
+            .as_ref()
+            .map(|color| (color.channels().3 * 100.0).round() as i64),
+        other_stuff: Default::default(),
+    })
 }
 
 fn new_glyph_from(glyph: &norad::Glyph) -> glyphs_plist::Glyph {
@@ -572,5 +1112,213 @@ fn new_glyph_from(glyph: &norad::Glyph) -> glyphs_plist::Glyph {
         other_stuff: Default::default(),
         left_kerning_group: None,
         right_kerning_group: None,
+        parts_settings: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    const SQUARE: &[(f64, f64)] = &[(100.0, 0.0), (400.0, 0.0), (400.0, 300.0), (100.0, 300.0)];
+
+    /// The brace layers of glyph "a", as (layer name, master name) pairs.
+    fn brace_layers(font: &glyphs_plist::Font) -> Vec<(String, String)> {
+        let master_name = |id: &str| {
+            font.font_master
+                .iter()
+                .find(|master| master.id == id)
+                .and_then(|master| master.name())
+                .unwrap()
+                .to_string()
+        };
+        let mut layers: Vec<(String, String)> = font.glyphs[0]
+            .layers
+            .iter()
+            .filter_map(|layer| {
+                let master_id = layer.associated_master_id.as_ref()?;
+                Some((layer.name.clone().unwrap(), master_name(master_id)))
+            })
+            .collect();
+        layers.sort();
+        layers
+    }
+
+    /// A sparse UFO, without master font info, with glyph "a".
+    fn sparse_ufo(style_name: &str) -> norad::Font {
+        let mut ufo = fixtures::ufo(style_name, vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        ufo.font_info.units_per_em = None;
+        ufo
+    }
+
+    #[test]
+    fn convert_in_memory() {
+        let glyphs = vec![
+            fixtures::ufo_glyph("a", 500.0, SQUARE),
+            fixtures::ufo_glyph("b", 600.0, SQUARE),
+        ];
+        let (designspace, ufos) = fixtures::masters(glyphs);
+
+        let font = convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        assert_eq!(font.family_name, "Test");
+        assert_eq!(font.font_master.len(), 2);
+        let names: Vec<&str> = font
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.glyphname.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+        // Every glyph has a layer for each master, in master order.
+        let master_ids: Vec<&str> = font.font_master.iter().map(|m| m.id.as_str()).collect();
+        for glyph in &font.glyphs {
+            let layer_ids: Vec<&str> = glyph.layers.iter().map(|l| l.layer_id.as_str()).collect();
+            assert_eq!(layer_ids, master_ids);
+        }
+        assert_eq!(font.glyphs[1].layers[0].width, 600.0);
+    }
+
+    #[test]
+    fn classify_sparse_sources() {
+        let glyphs = vec![
+            fixtures::ufo_glyph("a", 500.0, SQUARE),
+            fixtures::ufo_glyph("b", 500.0, SQUARE),
+        ];
+        let (mut designspace, mut ufos) = fixtures::masters(glyphs);
+        // A master with fewer glyphs than the default one is still a master.
+        let bold = ufos.get_mut(&fixtures::ufo_filename("Bold")).unwrap();
+        bold.default_layer_mut().remove_glyph("b");
+        bold.layers
+            .new_layer("alt")
+            .unwrap()
+            .insert_glyph(fixtures::ufo_glyph("a", 500.0, SQUARE));
+        designspace.sources.push(designspace::Source {
+            layer: Some("alt".into()),
+            filename: fixtures::ufo_filename("Bold"),
+            ..fixtures::source("Bold Alt", 450.0)
+        });
+        designspace
+            .sources
+            .push(fixtures::source("Semibold", 600.0));
+        ufos.insert(fixtures::ufo_filename("Semibold"), sparse_ufo("Semibold"));
+
+        let font = convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        let masters: Vec<&str> = font
+            .font_master
+            .iter()
+            .map(|master| master.name().unwrap())
+            .collect();
+        assert_eq!(masters, vec!["Regular", "Bold"]);
+        // A layer source belongs to the master of its UFO, even if another
+        // one is nearer, and a sparse UFO to the nearest master.
+        assert_eq!(
+            brace_layers(&font),
+            vec![
+                ("{450}".to_string(), "Bold".to_string()),
+                ("{600}".to_string(), "Bold".to_string()),
+            ]
+        );
+        assert_eq!(font.custom_parameters("Virtual Master").count(), 0);
+    }
+
+    #[test]
+    fn virtual_masters() {
+        let (mut designspace, mut ufos) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        designspace.axes[0].maximum = Some(900.0);
+        designspace.sources.push(fixtures::source("Black", 800.0));
+        ufos.insert(fixtures::ufo_filename("Black"), sparse_ufo("Black"));
+
+        let font = convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()).unwrap();
+        assert_eq!(
+            brace_layers(&font),
+            vec![("{800}".to_string(), "Bold".to_string())]
+        );
+        let virtual_masters: Vec<&Plist> = font.custom_parameters("Virtual Master").collect();
+        assert_eq!(virtual_masters.len(), 1);
+        let location = virtual_masters[0].as_array().unwrap()[0].as_dict().unwrap();
+        assert_eq!(location["Axis"], Plist::String("Weight".into()));
+        assert_eq!(location["Location"], Plist::Float(800.0));
+    }
+
+    fn location(weight: f32) -> Vec<designspace::Dimension> {
+        vec![designspace::Dimension {
+            name: "Weight".into(),
+            xvalue: Some(weight),
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn preflight_problems() {
+        let source = |name: &str, weight: f32| designspace::Source {
+            name: name.into(),
+            filename: format!("{}.ufo", name),
+            location: location(weight),
+            ..Default::default()
+        };
+        let designspace = designspace::DesignSpaceDocument {
+            axes: vec![designspace::Axis {
+                name: "Weight".into(),
+                tag: "wght".into(),
+                default: 400.0,
+                minimum: Some(400.0),
+                maximum: Some(700.0),
+                ..Default::default()
+            }],
+            sources: vec![
+                source("Regular", 400.0),
+                source("Bold", 700.0),
+                source("Bold", 800.0),
+            ],
+            instances: vec![
+                // Instances without a name are named by their style name.
+                designspace::Instance {
+                    stylename: Some("Heavy".into()),
+                    stylemapstylename: Some("heavy".into()),
+                    location: location(650.0),
+                    ..Default::default()
+                },
+                designspace::Instance {
+                    name: "Test Medium".into(),
+                    stylename: Some("Medium".into()),
+                    location: location(300.0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            preflight(&designspace),
+            vec![
+                "Source name Bold is used more than once.",
+                "Source Bold is at 800 on axis Weight, outside of its range 400 to 700.",
+                "Instance Heavy has unknown style map style name heavy.",
+                "Instance Test Medium is at 300 on axis Weight, outside of its range 400 to 700.",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_ufos_and_layers() {
+        let (mut designspace, ufos) =
+            fixtures::masters(vec![fixtures::ufo_glyph("a", 500.0, SQUARE)]);
+        designspace.sources.push(designspace::Source {
+            layer: Some("alt".into()),
+            filename: fixtures::ufo_filename("Bold"),
+            ..fixtures::source("Bold Alt", 450.0)
+        });
+        designspace.sources.push(fixtures::source("Black", 700.0));
+
+        match convert_to_glyphs(&designspace, &ufos, &Ufo2GlyphsOptions::default()) {
+            Err(Error::Validation(problems)) => assert_eq!(
+                problems,
+                vec![
+                    "Source Test Black uses UFO Test-Black.ufo, which is not loaded.",
+                    "Source Test Bold Alt uses layer alt, which does not exist in Test-Bold.ufo.",
+                ]
+            ),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+        }
     }
 }
@@ -0,0 +1,307 @@
+//! Design axes: their definitions, the mapping between user and design
+//! coordinates, and where the masters are on them.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::font::{Axis, Font, FontMaster};
+use crate::plist::Plist;
+
+/// An axis as a Designspace describes it. Locations are in user coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisDefinition {
+    pub name: String,
+    pub tag: String,
+    pub hidden: bool,
+    pub minimum: f64,
+    pub default: f64,
+    pub maximum: f64,
+    /// (user, design) pairs sorted by user location, empty if both
+    /// coordinates are the same.
+    pub mapping: Vec<(f64, f64)>,
+}
+
+impl Font {
+    /// The axes of the font: the Glyphs 3 `axes`, or the Glyphs 2 "Axes"
+    /// custom parameter.
+    pub fn font_axes(&self) -> Vec<Axis> {
+        if let Some(axes) = &self.axes {
+            return axes.clone();
+        }
+        self.custom_parameter("Axes")
+            .and_then(Plist::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|axis| {
+                Some(Axis {
+                    name: axis.get("Name")?.as_str()?.to_string(),
+                    tag: axis.get("Tag")?.as_str()?.to_string(),
+                    hidden: axis.get("Hidden").and_then(Plist::as_i64).map(|v| v != 0),
+                    other_stuff: HashMap::new(),
+                })
+            })
+            .collect()
+    }
+
+    /// The "Axis Mappings" custom parameter by axis tag, as (user, design)
+    /// pairs sorted by user location.
+    pub fn axis_mappings(&self) -> Result<HashMap<String, Vec<(f64, f64)>>, String> {
+        let Some(parameter) = self.custom_parameter("Axis Mappings") else {
+            return Ok(HashMap::new());
+        };
+        let mappings = parameter
+            .as_dict()
+            .ok_or("Axis Mappings must be a dictionary")?;
+        mappings
+            .iter()
+            .map(|(tag, mapping)| {
+                let mapping = mapping
+                    .as_dict()
+                    .ok_or_else(|| format!("Axis Mappings for {} must be a dictionary", tag))?;
+                let mut pairs = mapping
+                    .iter()
+                    .map(|(user, design)| {
+                        user.parse::<f64>()
+                            .ok()
+                            .zip(design.as_f64())
+                            .ok_or_else(|| {
+                                format!("invalid Axis Mappings entry {} for {}", user, tag)
+                            })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Ok((tag.clone(), pairs))
+            })
+            .collect()
+    }
+
+    /// The master at the default location: the one the "Variable Font
+    /// Origin" custom parameter names by ID or master name, or the first.
+    pub fn default_master(&self) -> Option<&FontMaster> {
+        self.custom_parameter("Variable Font Origin")
+            .and_then(Plist::as_str)
+            .and_then(|origin| {
+                self.font_master
+                    .iter()
+                    .find(|master| master.id == origin || master.name() == Some(origin))
+            })
+            .or_else(|| self.font_master.first())
+    }
+
+    /// The definitions of all axes. Mappings come from the "Axis Mappings"
+    /// custom parameter, or the "Axis Location" of the masters, and must be
+    /// monotonic and agree with where the masters are.
+    pub fn axis_definitions(&self) -> Result<Vec<AxisDefinition>, String> {
+        let axes = self.font_axes();
+        if !axes.is_empty() && self.font_master.is_empty() {
+            return Err("font has axes but no masters".into());
+        }
+        let mut mappings = self.axis_mappings()?;
+        let default_master = self.default_master();
+        let mut tags = HashSet::new();
+        axes.iter()
+            .enumerate()
+            .map(|(index, axis)| {
+                if !tags.insert(axis.tag.as_str()) {
+                    return Err(format!("duplicate axis tag {}", axis.tag));
+                }
+                // The (design, user) locations of the masters.
+                let locations: Vec<(f64, Option<f64>)> = self
+                    .font_master
+                    .iter()
+                    .map(|master| (master.axis_value(index), master.axis_location(&axis.name)))
+                    .collect();
+                let mut mapping = mappings.remove(&axis.tag).unwrap_or_else(|| {
+                    let mut pairs: Vec<(f64, f64)> = locations
+                        .iter()
+                        .filter_map(|&(design, user)| Some((user?, design)))
+                        .collect();
+                    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    pairs.dedup();
+                    pairs
+                });
+
+                for pair in mapping.windows(2) {
+                    let ((user_a, design_a), (user_b, design_b)) = (pair[0], pair[1]);
+                    if user_a == user_b {
+                        return Err(format!(
+                            "axis {}: user location {} maps to both {} and {}",
+                            axis.name, user_a, design_a, design_b
+                        ));
+                    }
+                    if design_b < design_a {
+                        return Err(format!(
+                            "axis {}: mapping is not monotonic at user location {}",
+                            axis.name, user_b
+                        ));
+                    }
+                }
+                for (master, &(design, user)) in self.font_master.iter().zip(&locations) {
+                    let Some(user) = user else {
+                        continue;
+                    };
+                    let mapped = map_value(&mapping, user);
+                    if (mapped - design).abs() > 0.5 {
+                        return Err(format!(
+                            "axis {}: master {} is at {} but its Axis Location {} maps to {}",
+                            axis.name, master.id, design, user, mapped
+                        ));
+                    }
+                }
+
+                let inverse: Vec<(f64, f64)> = mapping.iter().map(|&(u, d)| (d, u)).collect();
+                let users: Vec<f64> = locations
+                    .iter()
+                    .map(|&(design, _)| map_value(&inverse, design))
+                    .collect();
+                let minimum = users.iter().copied().fold(f64::INFINITY, f64::min);
+                let maximum = users.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let default = default_master
+                    .map(|master| map_value(&inverse, master.axis_value(index)))
+                    .unwrap_or(minimum);
+                if mapping.iter().all(|(user, design)| user == design) {
+                    mapping.clear();
+                }
+
+                Ok(AxisDefinition {
+                    name: axis.name.clone(),
+                    tag: axis.tag.clone(),
+                    hidden: axis.hidden.unwrap_or(false),
+                    minimum,
+                    default,
+                    maximum,
+                    mapping,
+                })
+            })
+            .collect()
+    }
+}
+
+impl FontMaster {
+    /// The design location of the master on the axis at `index`: from
+    /// `axes_values` in Glyphs 3, or the weight, width and custom values of
+    /// Glyphs 2, which default to 100, 100 and 0.
+    pub fn axis_value(&self, index: usize) -> f64 {
+        if let Some(values) = &self.axes_values {
+            return values.get(index).copied().unwrap_or(0.0);
+        }
+        let (value, default) = match index {
+            0 => (self.weight_value, 100.0),
+            1 => (self.width_value, 100.0),
+            2 => (self.custom_value, 0.0),
+            3 => (self.custom_value1, 0.0),
+            4 => (self.custom_value2, 0.0),
+            5 => (self.custom_value3, 0.0),
+            _ => (None, 0.0),
+        };
+        value.unwrap_or(default)
+    }
+
+    /// The user location of the master on the axis `axis_name`, from the
+    /// "Axis Location" custom parameter.
+    pub fn axis_location(&self, axis_name: &str) -> Option<f64> {
+        self.custom_parameter("Axis Location")?
+            .as_array()?
+            .iter()
+            .find(|location| location.get("Axis").and_then(Plist::as_str) == Some(axis_name))?
+            .get("Location")?
+            .as_f64()
+    }
+}
+
+/// Map `value` through the piecewise linear `mapping` of (input, output)
+/// pairs sorted by input. Values outside the mapping are shifted by the
+/// offset at its nearest end, like fontTools does.
+fn map_value(mapping: &[(f64, f64)], value: f64) -> f64 {
+    let (Some(&first), Some(&last)) = (mapping.first(), mapping.last()) else {
+        return value;
+    };
+    if value <= first.0 {
+        return value + first.1 - first.0;
+    }
+    if value >= last.0 {
+        return value + last.1 - last.0;
+    }
+    // A NaN value is in no segment, and stays NaN.
+    mapping
+        .windows(2)
+        .find(|segment| value < segment[1].0)
+        .map_or(value, |segment| {
+            let ((input_a, output_a), (input_b, output_b)) = (segment[0], segment[1]);
+            output_a + (output_b - output_a) * (value - input_a) / (input_b - input_a)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    fn font(custom_parameters: &str, masters: &str) -> Font {
+        FromPlist::from_plist(
+            Plist::parse(&format!(
+                r#"{{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                customParameters = ({});
+                fontMaster = ({});
+            }}"#,
+                custom_parameters, masters
+            ))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn axes_from_mappings_and_locations() {
+        let font = font(
+            r#"{name = Axes; value = ({Name = Weight; Tag = wght;}, {Hidden = 1; Name = Optical; Tag = opsz;});},
+            {name = "Axis Mappings"; value = {wght = {100 = 20; 400 = 80; 900 = 200;};};},
+            {name = "Variable Font Origin"; value = Regular;}"#,
+            r#"{id = m01; weightValue = 20; widthValue = 10;
+                customParameters = ({name = "Axis Location"; value = ({Axis = Weight; Location = 100;}, {Axis = Optical; Location = 8;});});},
+            {id = m02; weightValue = 80; widthValue = 10;
+                customParameters = ({name = "Master Name"; value = Regular;});},
+            {id = m03; weightValue = 140; widthValue = 72;
+                customParameters = ({name = "Axis Location"; value = ({Axis = Weight; Location = 650;}, {Axis = Optical; Location = 72;});});}"#,
+        );
+        let axes = font.axis_definitions().unwrap();
+        assert_eq!(axes.len(), 2);
+        assert_eq!(
+            (axes[0].minimum, axes[0].default, axes[0].maximum),
+            (100.0, 400.0, 650.0)
+        );
+        assert_eq!(
+            axes[0].mapping,
+            vec![(100.0, 20.0), (400.0, 80.0), (900.0, 200.0)]
+        );
+        assert!(!axes[0].hidden);
+        assert!(axes[1].hidden);
+        assert_eq!(axes[1].mapping, vec![(8.0, 10.0), (72.0, 72.0)]);
+        assert_eq!((axes[1].minimum, axes[1].maximum), (8.0, 72.0));
+    }
+
+    #[test]
+    fn inconsistent_axis_location() {
+        let font = font(
+            r#"{name = Axes; value = ({Name = Weight; Tag = wght;});},
+            {name = "Axis Mappings"; value = {wght = {100 = 20; 900 = 200;};};}"#,
+            r#"{id = m01; weightValue = 20;
+                customParameters = ({name = "Axis Location"; value = ({Axis = Weight; Location = 400;});});}"#,
+        );
+        assert!(font.axis_definitions().is_err());
+    }
+
+    #[test]
+    fn map_values() {
+        let mapping = [(100.0, 20.0), (400.0, 80.0), (900.0, 200.0)];
+        assert_eq!(map_value(&mapping, 250.0), 50.0);
+        assert_eq!(map_value(&mapping, 400.0), 80.0);
+        assert_eq!(map_value(&mapping, 50.0), -30.0);
+        assert_eq!(map_value(&mapping, 1000.0), 300.0);
+        assert!(map_value(&mapping, f64::NAN).is_nan());
+    }
+}
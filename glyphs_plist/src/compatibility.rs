@@ -0,0 +1,152 @@
+//! Checking that the master and brace layers of glyphs can be interpolated.
+
+use std::fmt;
+
+use crate::font::{Font, Glyph, Layer};
+
+/// A layer of a glyph that cannot be interpolated with the glyph's first
+/// master layer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Incompatibility {
+    pub glyph: String,
+    /// The name of the layer compared against.
+    pub reference_layer: String,
+    /// The name of the incompatible layer.
+    pub layer: String,
+    /// What differs, e.g. the first node with a different type.
+    pub problem: String,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "glyph '{}', layer '{}' vs. '{}': {}",
+            self.glyph, self.layer, self.reference_layer, self.problem
+        )
+    }
+}
+
+impl Font {
+    /// Check every glyph, see [`Font::check_glyph_compatibility`].
+    pub fn check_compatibility(&self) -> Vec<Incompatibility> {
+        self.glyphs
+            .iter()
+            .flat_map(|glyph| self.check_glyph_compatibility(glyph))
+            .collect()
+    }
+
+    /// Compare the master and brace layers of `glyph` to its first master
+    /// layer: paths, node types, components and anchors.
+    pub fn check_glyph_compatibility(&self, glyph: &Glyph) -> Vec<Incompatibility> {
+        let layers = self.interpolating_layers(glyph);
+        let Some((reference, rest)) = layers.split_first() else {
+            return Vec::new();
+        };
+        rest.iter()
+            .filter_map(|layer| {
+                let problem = reference.check_compatible(layer).err()?;
+                Some(Incompatibility {
+                    glyph: glyph.glyphname.to_string(),
+                    reference_layer: self.layer_name(reference),
+                    layer: self.layer_name(layer),
+                    problem,
+                })
+            })
+            .collect()
+    }
+
+    /// The master layers of `glyph`, in master order, then its brace layers.
+    fn interpolating_layers<'a>(&self, glyph: &'a Glyph) -> Vec<&'a Layer> {
+        let masters = self
+            .font_master
+            .iter()
+            .filter_map(|master| glyph.get_layer(&master.id));
+        let braces = glyph.layers.iter().filter(|layer| {
+            layer.associated_master_id.is_some()
+                && layer.bracket_ranges().is_none()
+                && layer.brace_coordinates().is_some()
+        });
+        masters.chain(braces).collect()
+    }
+
+    /// The name of a layer for humans: the master name for master layers.
+    fn layer_name(&self, layer: &Layer) -> String {
+        let master = self.font_master.iter().find(|m| m.id == layer.layer_id);
+        master
+            .and_then(|master| master.name())
+            .or(layer.name.as_deref())
+            .unwrap_or(&layer.layer_id)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, glyph};
+    use crate::font::{NodeType, Path};
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn layer(layer_id: &str, name: Option<&str>, types: &[NodeType]) -> Layer {
+        let mut path = Path::new(true);
+        for (i, &node_type) in types.iter().enumerate() {
+            path.add((i as f64 * 10.0, 0.0), node_type);
+        }
+        let square = fixtures::path(true, &[(0.0, 0.0); 4]);
+        Layer {
+            layer_id: layer_id.into(),
+            associated_master_id: name.map(|_| "m01".to_string()),
+            name: name.map(String::from),
+            paths: Some(vec![path, square]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reports_incompatible_layers() {
+        use NodeType::*;
+        let mut font: Font = FromPlist::from_plist(
+            Plist::parse(
+                r#"{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                fontMaster = (
+                    {id = m01; customParameters = ({name = "Master Name"; value = Light;});},
+                    {id = m02; customParameters = ({name = "Master Name"; value = Bold;});}
+                );
+            }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        font.glyphs.push(glyph(
+            "a",
+            vec![
+                layer("m01", None, &[Line, OffCurve, OffCurve, Curve]),
+                layer("m02", None, &[Line, OffCurve, OffCurve, Curve]),
+                layer("brace", Some("{150}"), &[Line, Line, OffCurve, Curve]),
+                layer("other", Some("Sketch"), &[Line]),
+            ],
+        ));
+
+        let problems = font.check_compatibility();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].reference_layer, "Light");
+        assert_eq!(problems[0].layer, "{150}");
+        assert!(problems[0].problem.starts_with("path 0 node 1 at (10, 0)"));
+
+        let mut reordered = font.glyphs[0].layers[1].clone();
+        reordered.paths.as_mut().unwrap().reverse();
+        font.glyphs[0].layers[1] = reordered;
+        let problems = font.check_compatibility();
+        assert_eq!(problems[0].layer, "Bold");
+        assert!(problems[0]
+            .problem
+            .ends_with("the paths may be in a different order"));
+    }
+}
@@ -0,0 +1,413 @@
+//! Resolving, aligning and decomposing components.
+
+use kurbo::{Affine, Point};
+
+use crate::font::{Component, Font, Glyph, Layer, Path};
+
+/// Components nested deeper than this are assumed to be cyclic and ignored.
+pub(crate) const MAX_COMPONENT_DEPTH: usize = 32;
+
+impl Path {
+    /// Transform all nodes of the path in place.
+    pub fn apply_affine(&mut self, affine: Affine) {
+        for node in self.nodes.iter_mut() {
+            node.pt = affine * node.pt;
+        }
+    }
+}
+
+impl Glyph {
+    /// Whether any layer of the glyph has both paths and components.
+    pub fn has_mixed_layers(&self) -> bool {
+        self.layers.iter().any(|layer| {
+            layer.paths.iter().flatten().next().is_some()
+                && layer.components.iter().flatten().next().is_some()
+        })
+    }
+}
+
+impl Font {
+    /// Return a copy of the given layer of a glyph with all components
+    /// replaced by their (transformed) outlines, recursively. See
+    /// [`Font::decompose`].
+    pub fn decompose_layer(&self, glyphname: &str, layer_id: &str) -> Option<Layer> {
+        let layer = self.get_glyph(glyphname)?.get_layer(layer_id)?;
+        Some(self.decompose(layer))
+    }
+
+    /// Return a copy of `layer` with all components replaced by their
+    /// (transformed) outlines, recursively.
+    ///
+    /// Components are resolved with [`Font::resolve_component`], so brace
+    /// and bracket layers pick up the corresponding layers of their base
+    /// glyphs, and smart components are instantiated at their piece values.
+    /// Components whose base glyph cannot be found are dropped.
+    pub fn decompose(&self, layer: &Layer) -> Layer {
+        let mut decomposed = layer.clone();
+        let paths = decomposed.paths.get_or_insert_with(Vec::new);
+        self.decompose_components_into(layer, layer, Affine::IDENTITY, 0, paths);
+        if paths.is_empty() {
+            decomposed.paths = None;
+        }
+        decomposed.components = None;
+        decomposed
+    }
+
+    /// Append the outlines of all components of `layer` to `paths`. `origin`
+    /// is the layer decomposition started from, which decides which layers of
+    /// the base glyphs to use.
+    fn decompose_components_into(
+        &self,
+        origin: &Layer,
+        layer: &Layer,
+        transform: Affine,
+        depth: usize,
+        paths: &mut Vec<Path>,
+    ) {
+        if depth >= MAX_COMPONENT_DEPTH {
+            return;
+        }
+        for component in layer.components.iter().flatten() {
+            let Some(base_layer) = self.component_layer(component, origin) else {
+                continue;
+            };
+            let transform = transform * component.transform.unwrap_or_default();
+            for path in base_layer.paths.iter().flatten() {
+                let mut path = path.clone();
+                path.apply_affine(transform);
+                paths.push(path);
+            }
+            self.decompose_components_into(origin, &base_layer, transform, depth + 1, paths);
+        }
+    }
+}
+
+/// Anchor names and positions, in the order Glyphs.app finds them.
+type Anchors = Vec<(String, Point)>;
+
+fn anchor_position(anchors: &Anchors, name: &str) -> Option<Point> {
+    anchors
+        .iter()
+        .find(|(anchor, _)| anchor == name)
+        .map(|(_, position)| *position)
+}
+
+/// Add an anchor, replacing the position of one with the same name.
+fn set_anchor(anchors: &mut Anchors, name: &str, position: Point) {
+    match anchors.iter_mut().find(|(anchor, _)| anchor == name) {
+        Some(anchor) => anchor.1 = position,
+        None => anchors.push((name.to_string(), position)),
+    }
+}
+
+impl Component {
+    /// Find the anchor pair this (mark) component attaches with: a "_name"
+    /// anchor of its base layer and the corresponding "name" anchor placed by
+    /// the preceding components, honouring an explicit `anchor` override.
+    fn attachment(&self, base_anchors: &Anchors, anchors: &Anchors) -> Option<(Point, Point)> {
+        let mut mark_anchors = base_anchors
+            .iter()
+            .filter_map(|(name, position)| Some((name.strip_prefix('_')?, *position)));
+
+        match &self.anchor {
+            Some(target) => {
+                // Ligature anchors like "top_2" attach the mark's "_top".
+                let stem = match target.rsplit_once('_') {
+                    Some((stem, index)) if index.parse::<u32>().is_ok() => stem,
+                    _ => target.as_str(),
+                };
+                let (_, mark_pt) = mark_anchors.find(|(name, _)| *name == stem)?;
+                Some((mark_pt, anchor_position(anchors, target)?))
+            }
+            None => mark_anchors
+                .find_map(|(name, mark_pt)| Some((mark_pt, anchor_position(anchors, name)?))),
+        }
+    }
+}
+
+impl Font {
+    /// Whether Glyphs.app aligns components automatically in this font.
+    pub fn automatic_alignment_enabled(&self) -> bool {
+        !self.disables_automatic_alignment.unwrap_or(false)
+    }
+
+    /// Return a copy of `layer` with its components positioned like
+    /// Glyphs.app's automatic alignment does, or `None` if no component of the
+    /// layer is automatically aligned.
+    ///
+    /// Aligned mark components are attached by anchor ("_top" to "top"),
+    /// including anchors base glyphs take from their own components, all
+    /// others are set side by side, and the layer takes the summed width
+    /// if its first component is aligned. Stored offsets of aligned components
+    /// are ignored, as in Glyphs.app. Layers with paths are only aligned where
+    /// a component forces it.
+    pub fn align_layer(&self, layer: &Layer) -> Option<Layer> {
+        self.align_components(layer, 0).0
+    }
+
+    /// Align the components of `layer` like [`Font::align_layer`], aligning
+    /// nested components first. Also returns the anchors of the layer: those
+    /// of its first component, the others of later components, except
+    /// "_name" ones, and its own, each replacing earlier ones of the same
+    /// name.
+    fn align_components(&self, layer: &Layer, depth: usize) -> (Option<Layer>, Anchors) {
+        let mut anchors = Anchors::new();
+        let components = match &layer.components {
+            Some(components) if depth < MAX_COMPONENT_DEPTH => components.as_slice(),
+            _ => &[],
+        };
+        let has_paths = layer.paths.iter().flatten().next().is_some();
+        let aligns_by_default = self.automatic_alignment_enabled() && !has_paths;
+
+        let mut aligned_components = Vec::with_capacity(components.len());
+        let mut advance = 0.0;
+        let mut any_aligned = false;
+        let mut first_aligned = false;
+        for (i, component) in components.iter().enumerate() {
+            let mut aligned_component = component.clone();
+            let Some(base_layer) = self.resolve_component(component, layer) else {
+                aligned_components.push(aligned_component);
+                continue;
+            };
+            let (aligned_base_layer, base_anchors) = self.align_components(base_layer, depth + 1);
+            let base_layer = aligned_base_layer.as_ref().unwrap_or(base_layer);
+
+            let is_aligned = match component.alignment {
+                Some(-1) => false,
+                Some(1) => true,
+                _ => aligns_by_default,
+            };
+            let mut transform = component.transform.unwrap_or_default();
+            if is_aligned {
+                any_aligned = true;
+                first_aligned |= i == 0;
+                let c = transform.as_coeffs();
+                let linear = Affine::new([c[0], c[1], c[2], c[3], 0.0, 0.0]);
+                transform = match component.attachment(&base_anchors, &anchors) {
+                    Some((mark_pt, base_pt)) => {
+                        Affine::translate(base_pt - linear * mark_pt) * linear
+                    }
+                    None => {
+                        let placed = Affine::translate((advance, 0.0)) * linear;
+                        advance += base_layer.width;
+                        placed
+                    }
+                };
+                aligned_component.transform = (component.transform.is_some()
+                    || transform != Affine::IDENTITY)
+                    .then_some(transform);
+            }
+
+            // Anchors of later components win, so stacked marks attach to
+            // the topmost mark.
+            for (name, position) in &base_anchors {
+                if i == 0 || !name.starts_with('_') {
+                    set_anchor(&mut anchors, name, transform * *position);
+                }
+            }
+            aligned_components.push(aligned_component);
+        }
+        for anchor in layer.anchors.iter().flatten() {
+            set_anchor(&mut anchors, &anchor.name, anchor.position);
+        }
+
+        if !any_aligned {
+            return (None, anchors);
+        }
+        let mut aligned = layer.clone();
+        aligned.components = Some(aligned_components);
+        if first_aligned {
+            aligned.width = advance;
+        }
+        (Some(aligned), anchors)
+    }
+
+    /// Apply [`Font::align_layer`] to every layer of every glyph, so that
+    /// stored component positions match what Glyphs.app displays.
+    pub fn apply_automatic_alignment(&mut self) {
+        let font = &*self;
+        let aligned: Vec<(usize, usize, Layer)> = font
+            .glyphs
+            .iter()
+            .enumerate()
+            .flat_map(|(glyph_index, glyph)| {
+                glyph
+                    .layers
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(layer_index, layer)| {
+                        Some((glyph_index, layer_index, font.align_layer(layer)?))
+                    })
+            })
+            .collect();
+        for (glyph_index, layer_index, layer) in aligned {
+            self.glyphs[glyph_index].layers[layer_index] = layer;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, anchor, component, font, glyph, path};
+
+    fn layer(paths: Option<Vec<Path>>, components: Option<Vec<Component>>) -> Layer {
+        fixtures::layer("m01", 500.0, paths, components)
+    }
+
+    #[test]
+    fn decompose_nested_components() {
+        let dot = path(true, &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        let font = font(vec![
+            glyph("dot", vec![layer(Some(vec![dot]), None)]),
+            glyph(
+                "twodots",
+                vec![layer(
+                    None,
+                    Some(vec![
+                        component("dot", Affine::IDENTITY),
+                        component("dot", Affine::translate((20.0, 0.0))),
+                    ]),
+                )],
+            ),
+            glyph(
+                "fourdots",
+                vec![layer(
+                    None,
+                    Some(vec![
+                        component("twodots", Affine::IDENTITY),
+                        component("twodots", Affine::translate((0.0, 100.0))),
+                        component("missing", Affine::IDENTITY),
+                    ]),
+                )],
+            ),
+        ]);
+
+        let decomposed = font.decompose_layer("fourdots", "m01").unwrap();
+        assert!(decomposed.components.is_none());
+        let paths = decomposed.paths.unwrap();
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths[3].nodes[0].pt, Point::new(20.0, 100.0));
+    }
+
+    #[test]
+    fn align_marks_by_anchor() {
+        let mut base = layer(None, None);
+        base.width = 400.0;
+        base.anchors = Some(vec![
+            anchor("top", 200.0, 500.0),
+            anchor("top_2", 300.0, 500.0),
+        ]);
+        let mut mark = layer(None, None);
+        mark.width = 0.0;
+        mark.anchors = Some(vec![
+            anchor("_top", -100.0, 450.0),
+            anchor("top", -100.0, 650.0),
+        ]);
+
+        let mut ligature_mark = component("acute", Affine::translate((7.0, 7.0)));
+        ligature_mark.anchor = Some("top_2".into());
+        let mut manual = component("acute", Affine::translate((1.0, 2.0)));
+        manual.alignment = Some(-1);
+        let composite = layer(
+            None,
+            Some(vec![
+                component("a", Affine::translate((50.0, 0.0))),
+                component("acute", Affine::IDENTITY),
+                component("acute", Affine::IDENTITY),
+                ligature_mark,
+                manual,
+            ]),
+        );
+
+        let mut font = font(vec![
+            glyph("a", vec![base]),
+            glyph("acute", vec![mark]),
+            glyph("composite", vec![composite]),
+        ]);
+        let aligned = font
+            .align_layer(
+                font.get_glyph("composite")
+                    .unwrap()
+                    .get_layer("m01")
+                    .unwrap(),
+            )
+            .unwrap();
+        let offsets: Vec<_> = aligned
+            .components
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|c| c.transform.unwrap().translation())
+            .collect();
+        assert_eq!(aligned.width, 400.0);
+        assert_eq!(offsets[0], (0.0, 0.0).into());
+        assert_eq!(offsets[1], (300.0, 50.0).into());
+        // Stacked on top of the first mark.
+        assert_eq!(offsets[2], (300.0, 250.0).into());
+        assert_eq!(offsets[3], (400.0, 50.0).into());
+        assert_eq!(offsets[4], (1.0, 2.0).into());
+
+        font.disables_automatic_alignment = Some(true);
+        assert!(font
+            .align_layer(
+                font.get_glyph("composite")
+                    .unwrap()
+                    .get_layer("m01")
+                    .unwrap()
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn align_marks_on_composite_base() {
+        let mut base = layer(None, None);
+        base.width = 600.0;
+        base.anchors = Some(vec![anchor("top", 300.0, 700.0)]);
+        let mut dieresis = layer(None, None);
+        dieresis.width = 0.0;
+        dieresis.anchors = Some(vec![anchor("_top", 0.0, 500.0), anchor("top", 0.0, 650.0)]);
+        let mut macron = layer(None, None);
+        macron.width = 0.0;
+        macron.anchors = Some(vec![anchor("_top", 0.0, 500.0), anchor("top", 0.0, 600.0)]);
+        // The stored offset of the dieresis is stale, as Glyphs.app ignores it.
+        let composite_base = layer(
+            None,
+            Some(vec![
+                component("A", Affine::IDENTITY),
+                component("dieresiscomb", Affine::IDENTITY),
+            ]),
+        );
+        let composite = layer(
+            None,
+            Some(vec![
+                component("Adieresis", Affine::IDENTITY),
+                component("macroncomb", Affine::IDENTITY),
+            ]),
+        );
+
+        let font = font(vec![
+            glyph("A", vec![base]),
+            glyph("dieresiscomb", vec![dieresis]),
+            glyph("macroncomb", vec![macron]),
+            glyph("Adieresis", vec![composite_base]),
+            glyph("Adieresismacron", vec![composite]),
+        ]);
+        let aligned = font
+            .align_layer(
+                font.get_glyph("Adieresismacron")
+                    .unwrap()
+                    .get_layer("m01")
+                    .unwrap(),
+            )
+            .unwrap();
+        let components = aligned.components.unwrap();
+        assert_eq!(aligned.width, 600.0);
+        let offsets: Vec<_> = components
+            .iter()
+            .map(|c| c.transform.unwrap().translation())
+            .collect();
+        assert_eq!(offsets, vec![(0.0, 0.0).into(), (300.0, 350.0).into()]);
+    }
+}
@@ -0,0 +1,450 @@
+//! Drawing corner, cap and segment components into the outlines.
+
+use std::iter::once;
+
+use kurbo::{Affine, CubicBez, ParamCurve, ParamCurveNearest, Point, Vec2};
+
+use crate::font::{Component, Font, Hint, HintType, Layer, Node, NodeType, Path};
+
+impl Font {
+    /// Return a copy of `layer` with its corner, cap and segment components
+    /// drawn into the paths they are attached to and their hints removed, or
+    /// `None` if the layer has none.
+    ///
+    /// The first open path of the component glyph is used. Corner components
+    /// are placed with their x-axis along the outgoing and their y-axis along
+    /// the incoming segment of the node, skewed to fit the angle, and the
+    /// adjacent segments are cut where the component path starts and ends.
+    /// Cap and segment components replace the segment following their node,
+    /// stretched along it, with their y-axis pointing out of a
+    /// counter-clockwise path.
+    pub fn apply_corner_components(&self, layer: &Layer) -> Result<Option<Layer>, String> {
+        let Some(hints) = &layer.hints else {
+            return Ok(None);
+        };
+        let mut components: Vec<&Hint> = hints.iter().filter(|h| h.is_component()).collect();
+        if components.is_empty() {
+            return Ok(None);
+        }
+        // Work from the last node backwards, so that inserted nodes don't
+        // shift the nodes the remaining components are attached to.
+        components.sort_by_key(|hint| std::cmp::Reverse(hint.origin_index()));
+
+        let mut applied = layer.clone();
+        let paths = applied.paths.get_or_insert_with(Vec::new);
+        // Segments wrapping around the end of a path remove the off-curves at
+        // its start, which shifts the nodes of the remaining components.
+        let mut removed = vec![0; paths.len()];
+        for hint in components {
+            let name = hint
+                .name
+                .as_deref()
+                .ok_or("corner component without a glyph name")?;
+            let (path_index, node_index) = hint
+                .origin_index()
+                .ok_or_else(|| format!("component '{}' has no valid origin", name))?;
+            let node_index = removed
+                .get(path_index)
+                .and_then(|&removed| node_index.checked_sub(removed));
+            let (path, node_index) = paths
+                .get_mut(path_index)
+                .zip(node_index)
+                .filter(|(path, node_index)| {
+                    matches!(path.nodes.get(*node_index),
+                        Some(node) if node.node_type != NodeType::OffCurve)
+                })
+                .ok_or_else(|| format!("component '{}' is attached to a missing node", name))?;
+
+            let component = Component {
+                name: name.into(),
+                ..Default::default()
+            };
+            let base_layer = self
+                .component_layer(&component, layer)
+                .ok_or_else(|| format!("cannot find glyph '{}'", name))?;
+            let shape = base_layer
+                .paths
+                .iter()
+                .flatten()
+                .find(|path| !path.closed && !path.nodes.is_empty())
+                .ok_or_else(|| format!("glyph '{}' has no open path", name))?;
+
+            let (scale_x, scale_y) = hint.scale_factors();
+            removed[path_index] += match hint.hint_type() {
+                HintType::Corner => insert_corner(
+                    path,
+                    node_index,
+                    shape,
+                    Affine::scale_non_uniform(scale_x, scale_y),
+                )
+                .map(|()| 0),
+                _ => replace_segment(path, node_index, shape, scale_y),
+            }
+            .map_err(|e| format!("component '{}': {}", name, e))?;
+        }
+
+        let hints: Vec<Hint> = hints
+            .iter()
+            .filter(|h| !h.is_component())
+            .cloned()
+            .collect();
+        applied.hints = (!hints.is_empty()).then_some(hints);
+        Ok(Some(applied))
+    }
+}
+
+/// Place a corner component at the on-curve node at `index`, replacing it.
+fn insert_corner(path: &mut Path, index: usize, shape: &Path, scale: Affine) -> Result<(), String> {
+    let (incoming, previous) = neighbours(path, index, -1).ok_or("node starts an open path")?;
+    let (outgoing, next) = neighbours(path, index, 1).ok_or("node ends an open path")?;
+    let corner = path.nodes[index].pt;
+    let in_pts: Vec<Point> = once(previous)
+        .chain(incoming.iter().rev().copied())
+        .chain(once(index))
+        .map(|i| path.nodes[i].pt)
+        .collect();
+    let out_pts: Vec<Point> = once(index)
+        .chain(outgoing.iter().copied())
+        .chain(once(next))
+        .map(|i| path.nodes[i].pt)
+        .collect();
+
+    let x_axis = direction(corner, &out_pts[1..])?;
+    let y_axis = direction(corner, in_pts[..in_pts.len() - 1].iter().rev())?;
+    let transform =
+        Affine::new([x_axis.x, x_axis.y, y_axis.x, y_axis.y, corner.x, corner.y]) * scale;
+
+    // The component path runs from the incoming to the outgoing segment,
+    // i.e. starts on the y-axis.
+    let start = shape.nodes[0].pt;
+    let mut nodes = open_nodes(shape, start.y.abs() < start.x.abs());
+    for node in nodes.iter_mut() {
+        node.pt = transform * node.pt;
+    }
+    let first = nodes[0].pt;
+    let last = nodes[nodes.len() - 1].pt;
+
+    let in_off_curves = cut_segment(&in_pts, first, true)?;
+    let out_off_curves = cut_segment(&out_pts, last, false)?;
+    for (&i, pt) in incoming.iter().rev().zip(in_off_curves) {
+        path.nodes[i].pt = pt;
+    }
+    for (&i, pt) in outgoing.iter().zip(out_off_curves) {
+        path.nodes[i].pt = pt;
+    }
+    nodes[0].node_type = if incoming.is_empty() {
+        NodeType::Line
+    } else {
+        NodeType::Curve
+    };
+    path.nodes.splice(index..=index, nodes);
+    Ok(())
+}
+
+/// Replace the segment following the on-curve node at `index` with a cap or
+/// segment component, and return the number of nodes removed from the start
+/// of the path.
+fn replace_segment(
+    path: &mut Path,
+    index: usize,
+    shape: &Path,
+    scale_y: f64,
+) -> Result<usize, String> {
+    let (outgoing, next) = neighbours(path, index, 1).ok_or("node ends an open path")?;
+    let start = path.nodes[index].pt;
+    let along = path.nodes[next].pt - start;
+    if along.hypot() < 1e-9 {
+        return Err("segment has zero length".into());
+    }
+
+    let (first, last) = (shape.nodes[0].pt, shape.nodes[shape.nodes.len() - 1].pt);
+    let nodes = open_nodes(shape, last.x < first.x);
+    let (x0, x1) = (nodes[0].pt.x, nodes[nodes.len() - 1].pt.x);
+    let length = x1 - x0;
+    if length.abs() < 1e-9 {
+        return Err("path starts and ends at the same x coordinate".into());
+    }
+    let normal = Vec2::new(along.y, -along.x).normalize() * scale_y;
+    let transform = Affine::new([along.x, along.y, normal.x, normal.y, start.x, start.y])
+        * Affine::new([1.0 / length, 0.0, 0.0, 1.0, -x0 / length, 0.0]);
+
+    let end_type = segment_kind(nodes[nodes.len() - 1].node_type);
+    let inner: Vec<Node> = nodes[1..nodes.len() - 1]
+        .iter()
+        .map(|node| Node {
+            pt: transform * node.pt,
+            ..node.clone()
+        })
+        .collect();
+    let (end, removed) = if next > index {
+        let inserted = inner.len();
+        path.nodes.splice(index + 1..next, inner);
+        (index + 1 + inserted, 0)
+    } else {
+        // The segment wraps around the end of the nodes list, so everything
+        // after the node and before the next on-curve node are off-curves.
+        debug_assert!(outgoing.len() == path.nodes.len() - index - 1 + next);
+        path.nodes.truncate(index + 1);
+        path.nodes.drain(..next);
+        path.nodes.extend(inner);
+        (0, next)
+    };
+    path.nodes[end].node_type = end_type;
+    Ok(removed)
+}
+
+/// Step `delta` nodes from `index`, wrapping around closed paths.
+fn step(path: &Path, index: usize, delta: isize) -> Option<usize> {
+    let len = path.nodes.len() as isize;
+    let i = index as isize + delta;
+    if path.closed {
+        Some(i.rem_euclid(len) as usize)
+    } else {
+        (0..len).contains(&i).then_some(i as usize)
+    }
+}
+
+/// The off-curve nodes from the node at `index` to the adjacent on-curve node
+/// in direction `delta`, nearest first, and the index of that on-curve node.
+fn neighbours(path: &Path, index: usize, delta: isize) -> Option<(Vec<usize>, usize)> {
+    let mut off_curves = Vec::new();
+    let mut i = index;
+    for _ in 0..path.nodes.len() {
+        i = step(path, i, delta)?;
+        if path.nodes[i].node_type != NodeType::OffCurve {
+            return Some((off_curves, i));
+        }
+        off_curves.push(i);
+    }
+    None
+}
+
+/// The unit vector from `from` to the first of `towards` that is not on it.
+fn direction<'a>(
+    from: Point,
+    towards: impl IntoIterator<Item = &'a Point>,
+) -> Result<Vec2, String> {
+    towards
+        .into_iter()
+        .map(|pt| *pt - from)
+        .find(|v| v.hypot() > 1e-9)
+        .map(Vec2::normalize)
+        .ok_or_else(|| "node has a zero-length segment".into())
+}
+
+/// Cut the line or cubic segment through `pts` at the point nearest to `at`,
+/// keeping the part before or after it, and return its new off-curve points.
+fn cut_segment(pts: &[Point], at: Point, keep_start: bool) -> Result<Vec<Point>, String> {
+    match *pts {
+        [_, _] => Ok(Vec::new()),
+        [p0, p1, p2, p3] => {
+            let cubic = CubicBez::new(p0, p1, p2, p3);
+            let t = cubic.nearest(at, 1e-6).t;
+            let part = if keep_start {
+                cubic.subsegment(0.0..t)
+            } else {
+                cubic.subsegment(t..1.0)
+            };
+            Ok(vec![part.p1, part.p2])
+        }
+        _ => Err("only line and cubic segments can be cut".into()),
+    }
+}
+
+/// The nodes of an open path, optionally reversed.
+fn open_nodes(path: &Path, reverse: bool) -> Vec<Node> {
+    if !reverse {
+        return path.nodes.clone();
+    }
+    // The type of an on-curve node describes the segment leading to it, so
+    // it moves to the other end of the segment.
+    let mut nodes = Vec::with_capacity(path.nodes.len());
+    let mut segment_type = NodeType::Line;
+    for node in path.nodes.iter().rev() {
+        if node.node_type == NodeType::OffCurve {
+            nodes.push(node.clone());
+        } else {
+            nodes.push(Node {
+                node_type: segment_type,
+                ..node.clone()
+            });
+            segment_type = segment_kind(node.node_type);
+        }
+    }
+    nodes
+}
+
+/// The node type without smoothness.
+fn segment_kind(node_type: NodeType) -> NodeType {
+    match node_type {
+        NodeType::LineSmooth => NodeType::Line,
+        NodeType::CurveSmooth => NodeType::Curve,
+        NodeType::QCurveSmooth => NodeType::QCurve,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, font, glyph, layer};
+    use crate::plist::Plist;
+    use crate::{FromPlist, ToPlist};
+
+    fn hint(r#type: &str, name: &str, origin: &str) -> Hint {
+        Hint {
+            r#type: Some(r#type.into()),
+            name: Some(name.into()),
+            origin: Some(FromPlist::from_plist(Plist::String(origin.into())).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply_corner_and_cap() {
+        let square = fixtures::path(
+            true,
+            &[(100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (0.0, 0.0)],
+        );
+
+        let font = font(vec![
+            glyph(
+                "_corner.chamfer",
+                vec![layer(
+                    "m01",
+                    0.0,
+                    Some(vec![fixtures::path(false, &[(20.0, 0.0), (0.0, 20.0)])]),
+                    None,
+                )],
+            ),
+            glyph(
+                "_cap.point",
+                vec![layer(
+                    "m01",
+                    100.0,
+                    Some(vec![fixtures::path(
+                        false,
+                        &[(0.0, 0.0), (50.0, 30.0), (100.0, 0.0)],
+                    )]),
+                    None,
+                )],
+            ),
+        ]);
+        let stem = Hint {
+            horizontal: Some(true),
+            ..Default::default()
+        };
+        let layer = Layer {
+            layer_id: "m01".into(),
+            paths: Some(vec![square]),
+            hints: Some(vec![
+                hint("Corner", "_corner.chamfer", "{0, 0}"),
+                hint("Cap", "_cap.point", "{0, 2}"),
+                stem,
+            ]),
+            ..Default::default()
+        };
+
+        let applied = font.apply_corner_components(&layer).unwrap().unwrap();
+        assert_eq!(applied.hints.unwrap().len(), 1);
+        let pts: Vec<Point> = applied.paths.unwrap()[0]
+            .nodes
+            .iter()
+            .map(|node| node.pt)
+            .collect();
+        assert_eq!(
+            pts,
+            vec![
+                Point::new(80.0, 0.0),
+                Point::new(100.0, 20.0),
+                Point::new(100.0, 100.0),
+                Point::new(0.0, 100.0),
+                Point::new(-30.0, 50.0),
+                Point::new(0.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_hint_indices() {
+        let mut hint = hint("corner", "_corner.x", "{1, 12}");
+        assert_eq!(hint.hint_type(), HintType::Corner);
+        assert_eq!(hint.origin_index(), Some((1, 12)));
+        hint.origin = Some(
+            FromPlist::from_plist(Plist::Array(vec![Plist::Integer(2), Plist::Integer(3)]))
+                .unwrap(),
+        );
+        hint.scale = Some(FromPlist::from_plist(Plist::String("{-1, 1.5}".into())).unwrap());
+        assert_eq!(hint.origin_index(), Some((2, 3)));
+        assert_eq!(hint.scale_factors(), (-1.0, 1.5));
+    }
+
+    #[test]
+    fn hint_indices_keep_format() {
+        for source in [
+            "{type = Corner; name = _corner.x; origin = \"{1, 12}\"; scale = \"{-1, 1.5}\";}",
+            "{type = Corner; name = _corner.x; origin = (1,12); scale = (-1,1.5);}",
+            "{horizontal = 1; origin = \"{0, 2}\"; target = up;}",
+            "{origin = (0,2); target = (0,5);}",
+        ] {
+            let plist = Plist::parse(source).unwrap();
+            let hint: Hint = FromPlist::from_plist(plist.clone()).unwrap();
+            assert_eq!(hint.to_plist().to_string(), plist.to_string());
+        }
+    }
+
+    #[test]
+    fn apply_cap_wrapping_around() {
+        let mut path = Path::new(true);
+        path.add((0.0, 33.0), NodeType::OffCurve);
+        path.add((0.0, 0.0), NodeType::Curve);
+        path.add((100.0, 0.0), NodeType::Line);
+        path.add((100.0, 100.0), NodeType::Line);
+        path.add((0.0, 100.0), NodeType::Line);
+        path.add((0.0, 66.0), NodeType::OffCurve);
+
+        let font = font(vec![glyph(
+            "_cap.flat",
+            vec![layer(
+                "m01",
+                100.0,
+                Some(vec![fixtures::path(
+                    false,
+                    &[(0.0, 0.0), (0.0, 10.0), (100.0, 10.0), (100.0, 0.0)],
+                )]),
+                None,
+            )],
+        )]);
+        // The cap after the last on-curve node removes the off-curve at the
+        // start of the path, which shifts the node of the other cap.
+        let layer = Layer {
+            layer_id: "m01".into(),
+            paths: Some(vec![path]),
+            hints: Some(vec![
+                hint("Cap", "_cap.flat", "{0, 2}"),
+                hint("Cap", "_cap.flat", "{0, 4}"),
+            ]),
+            ..Default::default()
+        };
+
+        let applied = font.apply_corner_components(&layer).unwrap().unwrap();
+        let nodes: Vec<(Point, NodeType)> = applied.paths.unwrap()[0]
+            .nodes
+            .iter()
+            .map(|node| (node.pt, node.node_type))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (Point::new(0.0, 0.0), NodeType::Line),
+                (Point::new(100.0, 0.0), NodeType::Line),
+                (Point::new(110.0, 0.0), NodeType::Line),
+                (Point::new(110.0, 100.0), NodeType::Line),
+                (Point::new(100.0, 100.0), NodeType::Line),
+                (Point::new(0.0, 100.0), NodeType::Line),
+                (Point::new(-10.0, 100.0), NodeType::Line),
+                (Point::new(-10.0, 0.0), NodeType::Line),
+            ]
+        );
+    }
+}
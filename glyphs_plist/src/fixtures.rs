@@ -0,0 +1,73 @@
+//! Fonts, glyphs and layers for tests, built in memory.
+
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point};
+
+use crate::font::{Anchor, Component, Font, Glyph, Layer, NodeType, Path};
+
+/// A font with the required fields filled in and `glyphs`.
+pub(crate) fn font(glyphs: Vec<Glyph>) -> Font {
+    Font {
+        family_name: "Test".into(),
+        version_major: 1,
+        version_minor: 0,
+        units_per_em: 1000,
+        glyphs,
+        ..Default::default()
+    }
+}
+
+/// A glyph named `name` with `layers`.
+pub(crate) fn glyph(name: &str, layers: Vec<Layer>) -> Glyph {
+    Glyph {
+        unicode: None,
+        layers,
+        glyphname: norad::Name::new(name).unwrap(),
+        left_kerning_group: None,
+        right_kerning_group: None,
+        parts_settings: None,
+        other_stuff: HashMap::new(),
+    }
+}
+
+/// A master layer with the given outlines.
+pub(crate) fn layer(
+    layer_id: &str,
+    width: f64,
+    paths: Option<Vec<Path>>,
+    components: Option<Vec<Component>>,
+) -> Layer {
+    Layer {
+        layer_id: layer_id.into(),
+        width,
+        paths,
+        components,
+        ..Default::default()
+    }
+}
+
+/// A path through `pts` made of line segments.
+pub(crate) fn path(closed: bool, pts: &[(f64, f64)]) -> Path {
+    let mut path = Path::new(closed);
+    for &pt in pts {
+        path.add(pt, NodeType::Line);
+    }
+    path
+}
+
+pub(crate) fn anchor(name: &str, x: f64, y: f64) -> Anchor {
+    Anchor {
+        name: name.into(),
+        position: Point::new(x, y),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn component(name: &str, transform: Affine) -> Component {
+    Component {
+        name: name.into(),
+        transform: Some(transform),
+        ..Default::default()
+    }
+}
@@ -8,12 +8,13 @@ use std::collections::HashMap;
 use std::fs;
 
 use kurbo::{Affine, Point};
+use log::warn;
 
-use crate::from_plist::FromPlist;
+use crate::from_plist::{FromPlist, FromPlistOpt};
 use crate::plist::Plist;
 use crate::to_plist::ToPlist;
 
-#[derive(Debug, FromPlist, ToPlist)]
+#[derive(Debug, Default, FromPlist, ToPlist)]
 pub struct Font {
     pub family_name: String,
     pub version_major: i64,
@@ -23,6 +24,14 @@ pub struct Font {
     pub font_master: Vec<FontMaster>,
     pub instances: Option<Vec<Instance>>,
     pub disables_automatic_alignment: Option<bool>,
+    /// The axes of a Glyphs 3 font.
+    pub axes: Option<Vec<Axis>>,
+    /// The stem definitions of a Glyphs 3 font.
+    pub stems: Option<Vec<Stem>>,
+    /// The names and other metadata of a Glyphs 3 font.
+    pub properties: Option<Vec<Property>>,
+    /// The metric definitions of a Glyphs 3 font.
+    pub metrics: Option<Vec<Metric>>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
@@ -36,11 +45,13 @@ pub struct Glyph {
     pub glyphname: norad::Name,
     pub left_kerning_group: Option<String>,
     pub right_kerning_group: Option<String>,
+    /// The axes of a smart glyph.
+    pub parts_settings: Option<Vec<PartProperty>>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
 
-#[derive(Clone, Debug, FromPlist, ToPlist)]
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
 pub struct Layer {
     pub name: Option<String>,
     pub associated_master_id: Option<String>,
@@ -50,6 +61,42 @@ pub struct Layer {
     pub components: Option<Vec<Component>>,
     pub anchors: Option<Vec<Anchor>>,
     pub guide_lines: Option<Vec<GuideLine>>,
+    /// For layers of smart glyphs, the pole of each smart axis the layer sits
+    /// on: 1 for the bottom value, 2 for the top value.
+    pub part_selection: Option<HashMap<String, i64>>,
+    pub hints: Option<Vec<Hint>>,
+    pub background: Option<Background>,
+    pub background_image: Option<BackgroundImage>,
+    /// The vertical advance, if it differs from the master's.
+    pub vert_width: Option<f64>,
+    /// See [`Layer::vertical_origin`].
+    pub vert_origin: Option<f64>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// The background of a layer, which only has outlines.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Background {
+    pub paths: Option<Vec<Path>>,
+    pub components: Option<Vec<Component>>,
+    pub anchors: Option<Vec<Anchor>>,
+    pub hints: Option<Vec<Hint>>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// An image placed behind the outlines of a layer.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct BackgroundImage {
+    /// The image file, relative to the Glyphs file.
+    pub image_path: String,
+    pub transform: Option<Affine>,
+    /// The visible part of the image, as "{{x, y}, {width, height}}".
+    pub crop: Option<Plist>,
+    pub locked: Option<bool>,
+    /// The opacity in percent (Glyphs 3).
+    pub alpha: Option<i64>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
@@ -58,12 +105,22 @@ pub struct Layer {
 pub struct Path {
     pub closed: bool,
     pub nodes: Vec<Node>,
+    /// Path attributes (Glyphs 3), which also keep UFO contour identifiers.
+    pub attr: Option<HashMap<String, Plist>>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Node {
     pub pt: Point,
     pub node_type: NodeType,
+    /// Where Glyphs.app keeps the node name, and UFO point identifiers are
+    /// kept.
+    pub user_data: Option<HashMap<String, Plist>>,
+    /// Whether the node was stored as a Glyphs 3 tuple, and is written as
+    /// one.
+    pub glyphs3: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -77,18 +134,95 @@ pub enum NodeType {
     QCurveSmooth,
 }
 
-#[derive(Clone, Debug, FromPlist, ToPlist)]
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
 pub struct Component {
     pub name: String,
     pub transform: Option<Affine>,
+    /// Automatic alignment override: -1 disables it for this component, 1
+    /// forces it even where Glyphs.app would not align automatically.
+    pub alignment: Option<i64>,
+    /// The name of the anchor to attach to, e.g. "top_2" for the second
+    /// component of a ligature.
+    pub anchor: Option<String>,
+    /// The smart component values, by smart axis name.
+    pub piece: Option<HashMap<String, f64>>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
 
+/// An axis of a smart glyph.
 #[derive(Clone, Debug, FromPlist, ToPlist)]
+pub struct PartProperty {
+    pub name: String,
+    pub bottom_value: f64,
+    pub top_value: f64,
+}
+
+/// A hint, including corner, cap and segment components.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Hint {
+    pub r#type: Option<String>,
+    /// The component glyph of corner, cap and segment components.
+    pub name: Option<String>,
+    pub origin: Option<NodeIndex>,
+    pub target: Option<HintTarget>,
+    /// The scale of corner, cap and segment components.
+    pub scale: Option<HintScale>,
+    pub horizontal: Option<bool>,
+    pub options: Option<i64>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HintType {
+    Stem,
+    Corner,
+    Cap,
+    Segment,
+    Other,
+}
+
+/// The (path, node) index of the node a hint is attached to, stored as a
+/// "{path, node}" string in Glyphs 2 and as an array in Glyphs 3.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeIndex {
+    pub path: usize,
+    pub node: usize,
+    /// Whether the index was stored as an array, and is written as one.
+    pub glyphs3: bool,
+}
+
+/// The node a stem hint ends at, or the direction of a ghost hint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HintTarget {
+    Node(NodeIndex),
+    Up,
+    Down,
+}
+
+/// The horizontal and vertical scale of a component hint, stored like a
+/// [`NodeIndex`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HintScale {
+    pub x: f64,
+    pub y: f64,
+    /// Whether the scale was stored as an array, and is written as one.
+    pub glyphs3: bool,
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Anchor {
     pub name: String,
     pub position: Point,
+    /// The alignment of the anchor in Glyphs 3 ("left", "center" or
+    /// "right"), for anchors that move with the width.
+    pub orientation: Option<String>,
+    pub user_data: Option<HashMap<String, Plist>>,
+    /// Whether the position was stored as a Glyphs 3 `pos` tuple, and is
+    /// written as one.
+    pub glyphs3: bool,
+    pub other_stuff: HashMap<String, Plist>,
 }
 
 #[derive(Clone, Debug, FromPlist, ToPlist)]
@@ -114,6 +248,85 @@ pub struct FontMaster {
     pub custom_value1: Option<f64>,
     pub custom_value2: Option<f64>,
     pub custom_value3: Option<f64>,
+    /// The location of a Glyphs 3 master, in the order of [`Font::axes`].
+    pub axes_values: Option<Vec<f64>>,
+    /// The alignment zones of a Glyphs 2 master. Glyphs 3 derives them from
+    /// the metric overshoots.
+    pub alignment_zones: Option<Vec<AlignmentZone>>,
+    pub horizontal_stems: Option<Vec<f64>>,
+    pub vertical_stems: Option<Vec<f64>>,
+    /// The stem values of a Glyphs 3 master, in the order of [`Font::stems`].
+    pub stem_values: Option<Vec<f64>>,
+    /// The metric values of a Glyphs 3 master, in the order of
+    /// [`Font::metrics`].
+    pub metric_values: Option<Vec<MetricValue>>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// A PostScript alignment zone: `size` units of overshoot from `position`,
+/// e.g. (800, 16), or (0, -16) for the baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AlignmentZone {
+    pub position: f64,
+    pub size: f64,
+}
+
+/// A metric definition of a Glyphs 3 font, like "x-height". Metrics with a
+/// filter only apply to some glyphs.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Metric {
+    pub r#type: Option<String>,
+    pub name: Option<String>,
+    pub filter: Option<String>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// The position and overshoot of a metric in a Glyphs 3 master.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct MetricValue {
+    pub pos: Option<f64>,
+    pub over: Option<f64>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// A font or instance property of Glyphs 3: a single value under `value`,
+/// or one per language under `values`.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Property {
+    pub key: String,
+    pub value: Option<String>,
+    pub values: Option<Vec<LocalizedValue>>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// The value of a property for a language, by OpenType language tag, or
+/// "dflt".
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct LocalizedValue {
+    pub language: String,
+    pub value: String,
+}
+
+/// A design axis. Glyphs 2 stores these in the "Axes" custom parameter,
+/// with capitalized keys.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Axis {
+    pub name: String,
+    pub tag: String,
+    pub hidden: Option<bool>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// A stem definition of a Glyphs 3 font.
+#[derive(Clone, Debug, Default, FromPlist, ToPlist)]
+pub struct Stem {
+    pub name: String,
+    pub horizontal: Option<bool>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
@@ -127,9 +340,13 @@ pub struct Instance {
     pub interpolation_custom1: Option<f64>,
     pub interpolation_custom2: Option<f64>,
     pub interpolation_custom3: Option<f64>,
+    /// The location of a Glyphs 3 instance, in the order of [`Font::axes`].
+    pub axes_values: Option<Vec<f64>>,
     pub is_bold: Option<bool>,
     pub is_italic: Option<bool>,
     pub link_style: Option<String>,
+    /// The localized names of a Glyphs 3 instance.
+    pub properties: Option<Vec<Property>>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
@@ -138,7 +355,7 @@ impl Font {
     pub fn load(path: &dyn AsRef<std::path::Path>) -> Result<Font, String> {
         let contents = std::fs::read_to_string(path).map_err(|e| format!("{:?}", e))?;
         let plist = Plist::parse(&contents).map_err(|e| format!("{:?}", e))?;
-        Ok(FromPlist::from_plist(plist))
+        FromPlist::from_plist(plist)
     }
 
     pub fn save(self, path: &std::path::Path) -> Result<(), String> {
@@ -162,14 +379,14 @@ impl Glyph {
 }
 
 impl FromPlist for norad::Name {
-    fn from_plist(plist: Plist) -> Self {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
         match plist {
             Plist::String(s) => Self::new(s.as_str())
-                .unwrap_or_else(|e| panic!("Cannot parse glyphname '{}': {:?}", s, e)),
+                .map_err(|e| format!("Cannot parse glyphname '{}': {:?}", s, e)),
             // Due to Glyphs.app quirks removing quotes around the name "infinity",
             // it is parsed as a float instead.
-            Plist::Float(f) if f.is_infinite() => Self::new("infinity").unwrap(),
-            _ => panic!("Cannot parse glyphname '{:?}'", plist),
+            Plist::Float(f) if f.is_infinite() => Ok(Self::new("infinity").unwrap()),
+            _ => Err(format!("Cannot parse glyphname '{:?}'", plist)),
         }
     }
 }
@@ -181,25 +398,26 @@ impl ToPlist for norad::Name {
 }
 
 impl FromPlist for norad::Codepoints {
-    fn from_plist(plist: Plist) -> Self {
-        let parse_str_as_char = |s: &str| -> char {
-            let cp = u32::from_str_radix(s, 16).unwrap();
-            char::try_from(cp).unwrap()
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        let parse_str_as_char = |s: &str| -> Result<char, String> {
+            u32::from_str_radix(s, 16)
+                .ok()
+                .and_then(|cp| char::try_from(cp).ok())
+                .ok_or_else(|| format!("Cannot parse codepoint '{}'", s))
         };
 
         match plist {
-            Plist::String(s) => norad::Codepoints::new(
-                s.split(',')
-                    .filter(|s| !s.trim().is_empty())
-                    .map(|cp| parse_str_as_char(cp)),
-            ),
+            Plist::String(s) => s
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(parse_str_as_char)
+                .collect::<Result<Vec<_>, _>>()
+                .map(norad::Codepoints::new),
             Plist::Integer(n) => {
-                let s = format!("{n}");
-                let cp = u32::from_str_radix(&s, 16).unwrap();
-                let cp = char::try_from(cp).unwrap();
-                norad::Codepoints::new([cp])
+                let cp = parse_str_as_char(&format!("{n}"))?;
+                Ok(norad::Codepoints::new([cp]))
             }
-            _ => panic!("Cannot parse codepoints: {:?}", plist),
+            _ => Err(format!("Cannot parse codepoints: {:?}", plist)),
         }
     }
 }
@@ -216,13 +434,84 @@ impl ToPlist for norad::Codepoints {
 }
 
 impl FromPlist for Node {
-    fn from_plist(plist: Plist) -> Self {
-        let mut spl = plist.as_str().unwrap().splitn(3, ' ');
-        let x = spl.next().unwrap().parse().unwrap();
-        let y = spl.next().unwrap().parse().unwrap();
-        let pt = Point::new(x, y);
-        let node_type = spl.next().unwrap().parse().unwrap();
-        Node { pt, node_type }
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match plist {
+            // Glyphs 3 writes nodes as (x, y, type, user data) tuples.
+            Plist::Array(mut fields) => {
+                let user_data = match fields.len() {
+                    3 => None,
+                    4 => Some(FromPlist::from_plist(fields.remove(3))?),
+                    _ => return Err(format!("Cannot parse node {:?}", fields)),
+                };
+                let pt = match (fields[0].as_f64(), fields[1].as_f64()) {
+                    (Some(x), Some(y)) => Point::new(x, y),
+                    _ => return Err(format!("Cannot parse node {:?}", fields)),
+                };
+                let node_type = match fields[2].as_str() {
+                    Some("l") => NodeType::Line,
+                    Some("ls") => NodeType::LineSmooth,
+                    Some("o") => NodeType::OffCurve,
+                    Some("c") => NodeType::Curve,
+                    Some("cs") => NodeType::CurveSmooth,
+                    Some("q") => NodeType::QCurve,
+                    Some("qs") => NodeType::QCurveSmooth,
+                    _ => return Err(format!("unknown node type {}", fields[2])),
+                };
+                Ok(Node {
+                    pt,
+                    node_type,
+                    user_data,
+                    glyphs3: true,
+                })
+            }
+            _ => {
+                let raw = plist
+                    .as_str()
+                    .ok_or_else(|| format!("Cannot parse node {:?}", plist))?;
+                // Glyphs 2 appends the user data as a dictionary. The node
+                // is still usable without it, so malformed user data is
+                // dropped rather than failing the whole file.
+                let (raw, user_data) = match raw.find('{') {
+                    Some(ix) => {
+                        let user_data = match Plist::parse(&raw[ix..])
+                            .map_err(|e| format!("{:?}", e))
+                            .and_then(FromPlist::from_plist)
+                        {
+                            Ok(user_data) => Some(user_data),
+                            Err(e) => {
+                                warn!("Dropping malformed user data of node '{}': {}", raw, e);
+                                None
+                            }
+                        };
+                        (raw[..ix].trim_end(), user_data)
+                    }
+                    None => (raw, None),
+                };
+                let mut spl = raw.splitn(3, ' ');
+                let (x, y, node_type) = (spl.next(), spl.next(), spl.next());
+                let x = x.and_then(|x| x.parse().ok());
+                let y = y.and_then(|y| y.parse().ok());
+                let (Some(x), Some(y)) = (x, y) else {
+                    return Err(format!("Cannot parse node '{}'", raw));
+                };
+                let node_type = node_type
+                    .ok_or_else(|| format!("Cannot parse node '{}'", raw))?
+                    .parse()?;
+                Ok(Node {
+                    pt: Point::new(x, y),
+                    node_type,
+                    user_data,
+                    glyphs3: false,
+                })
+            }
+        }
+    }
+}
+
+impl Node {
+    /// The name of the node, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.user_data.as_ref()?.get("name")?.as_str()
     }
 }
 
@@ -243,6 +532,18 @@ impl std::str::FromStr for NodeType {
 }
 
 impl NodeType {
+    fn glyphs3_str(&self) -> &'static str {
+        match self {
+            NodeType::Line => "l",
+            NodeType::LineSmooth => "ls",
+            NodeType::OffCurve => "o",
+            NodeType::Curve => "c",
+            NodeType::CurveSmooth => "cs",
+            NodeType::QCurve => "q",
+            NodeType::QCurveSmooth => "qs",
+        }
+    }
+
     fn glyphs_str(&self) -> &'static str {
         match self {
             NodeType::Line => "LINE",
@@ -258,24 +559,36 @@ impl NodeType {
 
 impl ToPlist for Node {
     fn to_plist(self) -> Plist {
-        format!(
+        let user_data = self.user_data.filter(|user_data| !user_data.is_empty());
+        if self.glyphs3 {
+            let mut fields = vec![
+                self.pt.x.into(),
+                self.pt.y.into(),
+                self.node_type.glyphs3_str().to_string().into(),
+            ];
+            fields.extend(user_data.map(Plist::Dictionary));
+            return fields.into();
+        }
+        let mut node = format!(
             "{} {} {}",
             self.pt.x,
             self.pt.y,
             self.node_type.glyphs_str()
-        )
-        .into()
+        );
+        if let Some(user_data) = user_data {
+            node.push(' ');
+            node.push_str(&Plist::Dictionary(user_data).to_string());
+        }
+        node.into()
     }
 }
 
 impl FromPlist for Affine {
-    fn from_plist(plist: Plist) -> Self {
-        let raw = plist.as_str().unwrap();
-        let raw = &raw[1..raw.len() - 1];
-        let coords: Vec<f64> = raw.split(", ").map(|c| c.parse().unwrap()).collect();
-        Affine::new([
-            coords[0], coords[1], coords[2], coords[3], coords[4], coords[5],
-        ])
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match parse_coords(&plist).as_deref() {
+            Some(&[a, b, c, d, e, f]) => Ok(Affine::new([a, b, c, d, e, f])),
+            _ => Err(format!("Cannot parse transform {:?}", plist)),
+        }
     }
 }
 
@@ -290,12 +603,57 @@ impl ToPlist for Affine {
     }
 }
 
+impl FromPlist for Anchor {
+    /// Glyphs 3 stores the position as a `pos` tuple, omitted at the origin.
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        let mut hashmap: HashMap<String, Plist> = FromPlist::from_plist(plist)?;
+        let glyphs3 = !hashmap.contains_key("position");
+        let position = hashmap
+            .remove("position")
+            .or_else(|| hashmap.remove("pos"))
+            .map(FromPlist::from_plist)
+            .transpose()?
+            .unwrap_or(Point::ZERO);
+        Ok(Anchor {
+            name: FromPlistOpt::from_plist(hashmap.remove("name"))
+                .map_err(|e| format!("name: {}", e))?,
+            position,
+            orientation: FromPlistOpt::from_plist(hashmap.remove("orientation"))
+                .map_err(|e| format!("orientation: {}", e))?,
+            user_data: FromPlistOpt::from_plist(hashmap.remove("userData"))
+                .map_err(|e| format!("userData: {}", e))?,
+            glyphs3,
+            other_stuff: hashmap,
+        })
+    }
+}
+
+impl ToPlist for Anchor {
+    fn to_plist(self) -> Plist {
+        let mut hashmap = self.other_stuff;
+        hashmap.insert("name".into(), self.name.into());
+        if !self.glyphs3 {
+            hashmap.insert("position".into(), self.position.to_plist());
+        } else if self.position != Point::ZERO {
+            let pos = vec![self.position.x.into(), self.position.y.into()];
+            hashmap.insert("pos".into(), Plist::Array(pos));
+        }
+        if let Some(orientation) = crate::to_plist::ToPlistOpt::to_plist(self.orientation) {
+            hashmap.insert("orientation".into(), orientation);
+        }
+        if let Some(user_data) = crate::to_plist::ToPlistOpt::to_plist(self.user_data) {
+            hashmap.insert("userData".into(), user_data);
+        }
+        hashmap.into()
+    }
+}
+
 impl FromPlist for Point {
-    fn from_plist(plist: Plist) -> Self {
-        let raw = plist.as_str().unwrap();
-        let raw = &raw[1..raw.len() - 1];
-        let coords: Vec<f64> = raw.split(", ").map(|c| c.parse().unwrap()).collect();
-        Point::new(coords[0], coords[1])
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        // Glyphs 3 writes points as tuples.
+        parse_pair(&plist)
+            .map(|(x, y)| Point::new(x, y))
+            .ok_or_else(|| format!("Cannot parse point {:?}", plist))
     }
 }
 
@@ -305,17 +663,103 @@ impl ToPlist for Point {
     }
 }
 
+impl FromPlist for NodeIndex {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        let (path, node) = parse_pair(&plist)
+            .filter(|&(path, node)| path >= 0.0 && node >= 0.0)
+            .ok_or_else(|| format!("Cannot parse node index {:?}", plist))?;
+        Ok(NodeIndex {
+            path: path as usize,
+            node: node as usize,
+            glyphs3: matches!(plist, Plist::Array(_)),
+        })
+    }
+}
+
+impl ToPlist for NodeIndex {
+    fn to_plist(self) -> Plist {
+        if self.glyphs3 {
+            vec![(self.path as i64).into(), (self.node as i64).into()].into()
+        } else {
+            format!("{{{}, {}}}", self.path, self.node).into()
+        }
+    }
+}
+
+impl FromPlist for HintTarget {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match plist.as_str() {
+            Some("up") => Ok(HintTarget::Up),
+            Some("down") => Ok(HintTarget::Down),
+            _ => FromPlist::from_plist(plist).map(HintTarget::Node),
+        }
+    }
+}
+
+impl ToPlist for HintTarget {
+    fn to_plist(self) -> Plist {
+        match self {
+            HintTarget::Node(index) => index.to_plist(),
+            HintTarget::Up => "up".to_string().into(),
+            HintTarget::Down => "down".to_string().into(),
+        }
+    }
+}
+
+impl FromPlist for HintScale {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        let (x, y) =
+            parse_pair(&plist).ok_or_else(|| format!("Cannot parse hint scale {:?}", plist))?;
+        Ok(HintScale {
+            x,
+            y,
+            glyphs3: matches!(plist, Plist::Array(_)),
+        })
+    }
+}
+
+impl ToPlist for HintScale {
+    fn to_plist(self) -> Plist {
+        if self.glyphs3 {
+            vec![self.x.into(), self.y.into()].into()
+        } else {
+            format!("{{{}, {}}}", self.x, self.y).into()
+        }
+    }
+}
+
+impl FromPlist for AlignmentZone {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        let (position, size) =
+            parse_pair(&plist).ok_or_else(|| format!("Cannot parse alignment zone {:?}", plist))?;
+        Ok(AlignmentZone { position, size })
+    }
+}
+
+impl ToPlist for AlignmentZone {
+    fn to_plist(self) -> Plist {
+        format!("{{{}, {}}}", self.position, self.size).into()
+    }
+}
+
 impl Path {
     pub fn new(closed: bool) -> Path {
         Path {
             nodes: Vec::new(),
             closed,
+            attr: None,
+            other_stuff: HashMap::new(),
         }
     }
 
     pub fn add(&mut self, pt: impl Into<Point>, node_type: NodeType) {
         let pt = pt.into();
-        self.nodes.push(Node { pt, node_type });
+        self.nodes.push(Node {
+            pt,
+            node_type,
+            user_data: None,
+            glyphs3: false,
+        });
     }
 
     /// Rotate left by one, placing the first point at the end. This is because
@@ -329,24 +773,201 @@ impl Path {
     }
 }
 
+impl Layer {
+    /// The background as a layer of its own, with the IDs and width of this
+    /// layer, or `None` if there is no background or it is empty.
+    pub fn background_layer(&self) -> Option<Layer> {
+        let background = self.background.as_ref()?;
+        if background.paths.iter().flatten().next().is_none()
+            && background.components.iter().flatten().next().is_none()
+            && background.anchors.iter().flatten().next().is_none()
+        {
+            return None;
+        }
+        Some(Layer {
+            name: self.name.clone(),
+            associated_master_id: self.associated_master_id.clone(),
+            layer_id: self.layer_id.clone(),
+            width: self.width,
+            paths: background.paths.clone(),
+            components: background.components.clone(),
+            anchors: background.anchors.clone(),
+            hints: background.hints.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Replace the background with the outlines of `layer`.
+    pub fn set_background_layer(&mut self, layer: Layer) {
+        let other_stuff = self
+            .background
+            .take()
+            .map(|background| background.other_stuff)
+            .unwrap_or_default();
+        self.background = Some(Background {
+            paths: layer.paths,
+            components: layer.components,
+            anchors: layer.anchors,
+            hints: layer.hints,
+            other_stuff,
+        });
+    }
+}
+
+impl Hint {
+    pub fn hint_type(&self) -> HintType {
+        match self
+            .r#type
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("stem") | None => HintType::Stem,
+            Some("corner") => HintType::Corner,
+            Some("cap") => HintType::Cap,
+            Some("segment") => HintType::Segment,
+            Some(_) => HintType::Other,
+        }
+    }
+
+    /// Whether the hint places a corner, cap or segment component.
+    pub fn is_component(&self) -> bool {
+        matches!(
+            self.hint_type(),
+            HintType::Corner | HintType::Cap | HintType::Segment
+        )
+    }
+
+    /// The (path, node) index the hint is attached to.
+    pub fn origin_index(&self) -> Option<(usize, usize)> {
+        self.origin.map(|origin| (origin.path, origin.node))
+    }
+
+    /// The horizontal and vertical scale of a corner, cap or segment
+    /// component, (1, 1) if unset.
+    pub fn scale_factors(&self) -> (f64, f64) {
+        self.scale
+            .map(|scale| (scale.x, scale.y))
+            .unwrap_or((1.0, 1.0))
+    }
+}
+
+/// Parse a number pair, either a "{a, b}" string or an array.
+pub(crate) fn parse_pair(plist: &Plist) -> Option<(f64, f64)> {
+    match parse_coords(plist)?.as_slice() {
+        &[a, b] => Some((a, b)),
+        _ => None,
+    }
+}
+
+/// The numbers of a Glyphs 2 `"{a, b, ...}"` string or a Glyphs 3 tuple.
+fn parse_coords(plist: &Plist) -> Option<Vec<f64>> {
+    match plist {
+        Plist::String(s) => s
+            .strip_prefix('{')?
+            .strip_suffix('}')?
+            .split(',')
+            .map(|c| c.trim().parse().ok())
+            .collect(),
+        Plist::Array(array) => array.iter().map(Plist::as_f64).collect(),
+        _ => None,
+    }
+}
+
 impl FontMaster {
-    pub fn name(&self) -> &str {
+    /// The name of the master: the Glyphs 3 `name`, or the Glyphs 2 "Master
+    /// Name" custom parameter.
+    pub fn name(&self) -> Option<&str> {
         self.other_stuff
-            .get("customParameters")
-            .map(|cps| {
-                cps.as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|cp| cp.as_dict().unwrap())
-            })
-            .and_then(|mut cps| {
-                cps.find(|cp| cp.get("name").unwrap().as_str().unwrap() == "Master Name")
-            })
-            .and_then(|cp| cp.get("value").unwrap().as_str())
-            .expect("Cannot determine name for master")
+            .get("name")
+            .or_else(|| self.custom_parameter("Master Name"))
+            .and_then(Plist::as_str)
+    }
+
+    /// The value of the master custom parameter `name`.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        custom_parameter(&self.other_stuff, name)
+    }
+
+    /// Set the master custom parameter `name`, replacing an existing one.
+    pub fn set_custom_parameter(&mut self, name: &str, value: Plist) {
+        set_custom_parameter(&mut self.other_stuff, name, value, true);
     }
 }
 
+impl Instance {
+    /// The value of the instance custom parameter `name`.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        custom_parameter(&self.other_stuff, name)
+    }
+}
+
+impl Font {
+    /// The value of the font custom parameter `name`.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        custom_parameter(&self.other_stuff, name)
+    }
+
+    /// The values of all font custom parameters `name`, for parameters that
+    /// can be given more than once.
+    pub fn custom_parameters<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Plist> {
+        custom_parameters(&self.other_stuff, name)
+    }
+
+    /// Set the font custom parameter `name`, replacing an existing one.
+    pub fn set_custom_parameter(&mut self, name: &str, value: Plist) {
+        set_custom_parameter(&mut self.other_stuff, name, value, true);
+    }
+
+    /// Add a font custom parameter `name`, keeping existing ones.
+    pub fn add_custom_parameter(&mut self, name: &str, value: Plist) {
+        set_custom_parameter(&mut self.other_stuff, name, value, false);
+    }
+}
+
+fn custom_parameter<'a>(other_stuff: &'a HashMap<String, Plist>, name: &str) -> Option<&'a Plist> {
+    other_stuff
+        .get("customParameters")?
+        .as_array()?
+        .iter()
+        .find(|cp| cp.get("name").and_then(Plist::as_str) == Some(name))?
+        .get("value")
+}
+
+fn custom_parameters<'a>(
+    other_stuff: &'a HashMap<String, Plist>,
+    name: &'a str,
+) -> impl Iterator<Item = &'a Plist> {
+    other_stuff
+        .get("customParameters")
+        .and_then(Plist::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter(move |cp| cp.get("name").and_then(Plist::as_str) == Some(name))
+        .filter_map(|cp| cp.get("value"))
+}
+
+fn set_custom_parameter(
+    other_stuff: &mut HashMap<String, Plist>,
+    name: &str,
+    value: Plist,
+    replace: bool,
+) {
+    let mut parameters = match other_stuff.remove("customParameters") {
+        Some(Plist::Array(parameters)) => parameters,
+        // Glyphs.app cannot read anything but a list here either.
+        _ => Vec::new(),
+    };
+    if replace {
+        parameters.retain(|cp| cp.get("name").and_then(Plist::as_str) != Some(name));
+    }
+    parameters.push(Plist::Dictionary(HashMap::from([
+        ("name".to_string(), name.to_string().into()),
+        ("value".to_string(), value),
+    ])));
+    other_stuff.insert("customParameters".into(), parameters.into());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -360,4 +981,174 @@ mod tests {
     fn parse_empty_font_glyphs3() {
         Font::load(&"../testdata/NewFontG3.glyphs").unwrap();
     }
+
+    #[test]
+    fn parse_anchor_forms() {
+        let plist = Plist::parse(
+            r#"(
+                {name = top; position = "{250, 700}";},
+                {name = bottom; pos = (250,-10); orientation = center; userData = {a = 1;};},
+                {name = origin;}
+            )"#,
+        )
+        .unwrap();
+        let anchors: Vec<Anchor> = FromPlist::from_plist(plist).unwrap();
+        assert_eq!(anchors[0].position, Point::new(250.0, 700.0));
+        assert_eq!(anchors[1].position, Point::new(250.0, -10.0));
+        assert_eq!(anchors[1].orientation.as_deref(), Some("center"));
+        assert_eq!(
+            anchors[1].user_data.as_ref().unwrap()["a"],
+            Plist::Integer(1)
+        );
+        assert_eq!(anchors[2].position, Point::ZERO);
+
+        let written = anchors[0].clone().to_plist();
+        let written = written.as_dict().unwrap();
+        assert_eq!(written["position"], Plist::String("{250, 700}".into()));
+        assert!(!written.contains_key("pos"));
+
+        let written = anchors[1].clone().to_plist();
+        let written = written.as_dict().unwrap();
+        assert_eq!(
+            written["pos"],
+            Plist::Array(vec![Plist::Float(250.0), Plist::Float(-10.0)])
+        );
+        assert!(!written.contains_key("position"));
+    }
+
+    #[test]
+    fn anchors_glyphs3_roundtrip() {
+        let plist = Plist::parse(
+            r#"(
+                {name = top; pos = (250,700);},
+                {name = origin;}
+            )"#,
+        )
+        .unwrap();
+        let anchors: Vec<Anchor> = FromPlist::from_plist(plist.clone()).unwrap();
+        assert_eq!(anchors.to_plist().to_string(), plist.to_string());
+    }
+
+    #[test]
+    fn parse_node_user_data() {
+        let plist = Plist::parse(
+            r#"("354 0 LINE {\nname = hr00;\n}", "10 20 OFFCURVE", (5,6,cs,{name = tip;}))"#,
+        )
+        .unwrap();
+        let nodes: Vec<Node> = FromPlist::from_plist(plist).unwrap();
+        assert_eq!(nodes[0].pt, Point::new(354.0, 0.0));
+        assert_eq!(nodes[0].node_type, NodeType::Line);
+        assert_eq!(nodes[0].name(), Some("hr00"));
+        assert_eq!(nodes[1].name(), None);
+        assert_eq!(nodes[2].node_type, NodeType::CurveSmooth);
+        assert_eq!(nodes[2].name(), Some("tip"));
+
+        let written = nodes[0].clone().to_plist();
+        let reparsed: Node = FromPlist::from_plist(written).unwrap();
+        assert_eq!(reparsed.name(), Some("hr00"));
+    }
+
+    #[test]
+    fn nodes_keep_format() {
+        let plist = Plist::parse(r#"("10 20 OFFCURVE", (5,6,cs,{name = tip;}), (7,8,l))"#).unwrap();
+        let nodes: Vec<Node> = FromPlist::from_plist(plist.clone()).unwrap();
+        assert_eq!(nodes.to_plist().to_string(), plist.to_string());
+    }
+
+    #[test]
+    fn drop_malformed_node_user_data() {
+        let plist = Plist::parse(r#"("354 0 LINE {name = ;")"#).unwrap();
+        let nodes: Vec<Node> = FromPlist::from_plist(plist).unwrap();
+        assert_eq!(nodes[0].pt, Point::new(354.0, 0.0));
+        assert_eq!(nodes[0].node_type, NodeType::Line);
+        assert!(nodes[0].user_data.is_none());
+    }
+
+    #[test]
+    fn master_name() {
+        let masters: Vec<FontMaster> = FromPlist::from_plist(
+            Plist::parse(
+                r#"(
+                    {id = m01; name = Bold; customParameters = ({name = "Master Name"; value = Old;});},
+                    {id = m02; customParameters = ({name = "Master Name"; value = Light;});},
+                    {id = m03;}
+                )"#,
+            )
+            .unwrap(),
+        ).unwrap();
+        let names: Vec<Option<&str>> = masters.iter().map(FontMaster::name).collect();
+        assert_eq!(names, [Some("Bold"), Some("Light"), None]);
+    }
+
+    #[test]
+    fn malformed_values() {
+        fn parse<T: FromPlist>(source: &str) -> Result<T, String> {
+            FromPlist::from_plist(Plist::parse(source).unwrap())
+        }
+        assert!(parse::<Node>(r#""10 LINE""#).is_err());
+        assert!(parse::<Node>("(10,20)").is_err());
+        assert!(parse::<AlignmentZone>(r#""{800}""#).is_err());
+        assert!(parse::<HintScale>("(1,2,3)").is_err());
+        assert!(parse::<NodeIndex>("(-1,2)").is_err());
+        assert_eq!(
+            parse::<Anchor>("{pos = (1,2);}").unwrap_err(),
+            "name: missing"
+        );
+    }
+
+    #[test]
+    fn set_custom_parameter() {
+        let plist = Plist::parse(r#"{id = m01; customParameters = "not a list";}"#).unwrap();
+        let mut master: FontMaster = FromPlist::from_plist(plist).unwrap();
+        assert_eq!(master.custom_parameter("blueScale"), None);
+
+        master.set_custom_parameter("blueScale", 0.05.into());
+        master.set_custom_parameter("blueShift", 7i64.into());
+        master.set_custom_parameter("blueScale", 0.04.into());
+        assert_eq!(
+            master.custom_parameter("blueScale"),
+            Some(&Plist::Float(0.04))
+        );
+        assert_eq!(
+            master.custom_parameter("blueShift"),
+            Some(&Plist::Integer(7))
+        );
+        assert_eq!(
+            master.other_stuff["customParameters"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn layer_background_roundtrip() {
+        let plist = Plist::parse(
+            r#"{
+                layerId = m01;
+                width = 500;
+                background = {
+                    anchors = ({name = top; position = "{250, 700}";});
+                    paths = ({closed = 1; nodes = ("0 0 LINE", "100 0 LINE", "100 100 LINE");});
+                };
+            }"#,
+        )
+        .unwrap();
+        let layer: Layer = FromPlist::from_plist(plist).unwrap();
+        let background = layer.background_layer().unwrap();
+        assert_eq!(background.width, 500.0);
+        assert_eq!(background.paths.as_ref().unwrap()[0].nodes.len(), 3);
+
+        let mut restored = Layer {
+            layer_id: "m01".into(),
+            ..Default::default()
+        };
+        restored.set_background_layer(background);
+        let restored = restored.to_plist();
+        assert_eq!(
+            restored.as_dict().unwrap()["background"],
+            layer.to_plist().as_dict().unwrap()["background"]
+        );
+    }
 }
@@ -1,66 +1,90 @@
+use std::collections::HashMap;
+
 pub use glyphs_plist_derive::FromPlist;
 
 use crate::plist::Plist;
 
-pub trait FromPlist {
-    // Consider using result type; just unwrap for now.
-    fn from_plist(plist: Plist) -> Self;
+/// Conversion from a parsed plist. Errors describe what was expected,
+/// prefixed with the keys leading to it.
+pub trait FromPlist: Sized {
+    fn from_plist(plist: Plist) -> Result<Self, String>;
 }
 
-pub trait FromPlistOpt {
-    // Consider using result type; just unwrap for now.
-    fn from_plist(plist: Option<Plist>) -> Self;
+pub trait FromPlistOpt: Sized {
+    fn from_plist(plist: Option<Plist>) -> Result<Self, String>;
 }
 
 impl FromPlist for Plist {
-    fn from_plist(plist: Plist) -> Self {
-        plist
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        Ok(plist)
     }
 }
 
 impl FromPlist for String {
-    fn from_plist(plist: Plist) -> Self {
-        plist.into_string()
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match plist {
+            Plist::String(s) => Ok(s),
+            _ => Err(format!("expected string, found {}", plist)),
+        }
     }
 }
 
 impl FromPlist for bool {
-    fn from_plist(plist: Plist) -> Self {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
         // TODO: maybe error or warn on values other than 0, 1
-        plist.as_i64().expect("expected integer") != 0
+        <i64 as FromPlist>::from_plist(plist).map(|i| i != 0)
     }
 }
 
 impl FromPlist for i64 {
-    fn from_plist(plist: Plist) -> Self {
-        plist.as_i64().expect("expected integer")
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        plist
+            .as_i64()
+            .ok_or_else(|| format!("expected integer, found {}", plist))
     }
 }
 
 impl FromPlist for f64 {
-    fn from_plist(plist: Plist) -> Self {
-        plist.as_f64().expect("expected float")
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        plist
+            .as_f64()
+            .ok_or_else(|| format!("expected float, found {}", plist))
     }
 }
 
 impl<T: FromPlist> FromPlist for Vec<T> {
-    fn from_plist(plist: Plist) -> Self {
-        let mut result = Vec::new();
-        for element in plist.into_vec() {
-            result.push(FromPlist::from_plist(element));
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match plist {
+            Plist::Array(array) => array.into_iter().map(FromPlist::from_plist).collect(),
+            _ => Err("expected array".into()),
+        }
+    }
+}
+
+impl<T: FromPlist> FromPlist for HashMap<String, T> {
+    fn from_plist(plist: Plist) -> Result<Self, String> {
+        match plist {
+            Plist::Dictionary(dict) => dict
+                .into_iter()
+                .map(|(key, value)| {
+                    let value =
+                        FromPlist::from_plist(value).map_err(|e| format!("{}: {}", key, e))?;
+                    Ok((key, value))
+                })
+                .collect(),
+            _ => Err("expected dictionary".into()),
         }
-        result
     }
 }
 
 impl<T: FromPlist> FromPlistOpt for T {
-    fn from_plist(plist: Option<Plist>) -> Self {
-        FromPlist::from_plist(plist.unwrap())
+    fn from_plist(plist: Option<Plist>) -> Result<Self, String> {
+        FromPlist::from_plist(plist.ok_or("missing")?)
     }
 }
 
 impl<T: FromPlist> FromPlistOpt for Option<T> {
-    fn from_plist(plist: Option<Plist>) -> Self {
-        plist.map(FromPlist::from_plist)
+    fn from_plist(plist: Option<Plist>) -> Result<Self, String> {
+        plist.map(FromPlist::from_plist).transpose()
     }
 }
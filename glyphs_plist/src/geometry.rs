@@ -0,0 +1,267 @@
+//! Geometry queries on paths and layers, backed by kurbo.
+
+use kurbo::{Affine, BezPath, Point, Rect, Shape};
+
+use crate::components::MAX_COMPONENT_DEPTH;
+use crate::font::{Component, Font, Layer, NodeType, Path};
+
+impl Path {
+    /// Convert the path to a kurbo [`BezPath`].
+    ///
+    /// Closed paths start at their last node, like Glyphs.app draws them.
+    /// Quadratic runs of off-curve points get their implied on-curve points
+    /// inserted, TrueType-style.
+    pub fn to_bez_path(&self) -> BezPath {
+        let mut bez = BezPath::new();
+
+        let Some(start) = self
+            .nodes
+            .iter()
+            .rposition(|node| node.node_type != NodeType::OffCurve)
+        else {
+            // A closed TrueType contour without any on-curve points: every
+            // on-curve point is implied halfway between two off-curves.
+            if self.closed && !self.nodes.is_empty() {
+                let pts: Vec<Point> = self.nodes.iter().map(|node| node.pt).collect();
+                let n = pts.len();
+                bez.move_to(pts[n - 1].midpoint(pts[0]));
+                for i in 0..n {
+                    bez.quad_to(pts[i], pts[i].midpoint(pts[(i + 1) % n]));
+                }
+                bez.close_path();
+            }
+            return bez;
+        };
+
+        let nodes: Vec<_> = if self.closed {
+            // Go all the way round, ending on the start node again.
+            self.nodes[start + 1..]
+                .iter()
+                .chain(self.nodes[..=start].iter())
+                .collect()
+        } else {
+            // Leading off-curves of an open path have nothing to attach to.
+            let first = self
+                .nodes
+                .iter()
+                .position(|node| node.node_type != NodeType::OffCurve)
+                .unwrap();
+            self.nodes[first + 1..].iter().collect()
+        };
+        let start_pt = if self.closed {
+            self.nodes[start].pt
+        } else {
+            self.nodes
+                .iter()
+                .find(|node| node.node_type != NodeType::OffCurve)
+                .unwrap()
+                .pt
+        };
+
+        bez.move_to(start_pt);
+        let mut off_curves: Vec<Point> = Vec::new();
+        for node in nodes {
+            match node.node_type {
+                NodeType::OffCurve => {
+                    off_curves.push(node.pt);
+                    continue;
+                }
+                NodeType::Line | NodeType::LineSmooth => bez.line_to(node.pt),
+                NodeType::Curve | NodeType::CurveSmooth => match off_curves.as_slice() {
+                    [] => bez.line_to(node.pt),
+                    [p1] => bez.quad_to(*p1, node.pt),
+                    [p1, .., p2] => bez.curve_to(*p1, *p2, node.pt),
+                },
+                NodeType::QCurve | NodeType::QCurveSmooth => {
+                    if off_curves.is_empty() {
+                        bez.line_to(node.pt);
+                    }
+                    for (i, p1) in off_curves.iter().enumerate() {
+                        let p2 = match off_curves.get(i + 1) {
+                            Some(next) => p1.midpoint(*next),
+                            None => node.pt,
+                        };
+                        bez.quad_to(*p1, p2);
+                    }
+                }
+            }
+            off_curves.clear();
+        }
+        if self.closed {
+            bez.close_path();
+        }
+
+        bez
+    }
+
+    /// The bounding box of the outline (not just of the nodes), or `None` for
+    /// an empty path.
+    pub fn bbox(&self) -> Option<Rect> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        Some(self.to_bez_path().bounding_box())
+    }
+
+    /// The signed area of the path. Positive for counter-clockwise paths
+    /// (in the y-up coordinate system of fonts), negative for clockwise ones.
+    pub fn area(&self) -> f64 {
+        self.to_bez_path().area()
+    }
+
+    /// Whether the path runs clockwise. Glyphs.app expects outer contours to
+    /// run counter-clockwise.
+    pub fn is_clockwise(&self) -> bool {
+        self.area() < 0.0
+    }
+
+    /// Whether the point lies inside the path, using the non-zero winding
+    /// rule. Only meaningful for closed paths.
+    pub fn contains(&self, pt: Point) -> bool {
+        self.to_bez_path().contains(pt)
+    }
+}
+
+impl Layer {
+    /// The ID of the master this layer belongs to: its own ID for master
+    /// layers, the associated master ID for all others.
+    pub fn master_id(&self) -> &str {
+        self.associated_master_id
+            .as_deref()
+            .unwrap_or(&self.layer_id)
+    }
+
+    /// The outline of the layer, with all components resolved against `font`
+    /// and their transformations applied.
+    pub fn to_bez_path(&self, font: &Font) -> BezPath {
+        let mut bez = BezPath::new();
+        self.extend_bez_path(font, Affine::IDENTITY, 0, &mut bez);
+        bez
+    }
+
+    fn extend_bez_path(&self, font: &Font, transform: Affine, depth: usize, bez: &mut BezPath) {
+        for path in self.paths.iter().flatten() {
+            let mut path = path.to_bez_path();
+            path.apply_affine(transform);
+            bez.extend(path);
+        }
+        if depth >= MAX_COMPONENT_DEPTH {
+            return;
+        }
+        for component in self.components.iter().flatten() {
+            if let Some(base_layer) = font.component_layer(component, self) {
+                let transform = transform * component.transform.unwrap_or_default();
+                base_layer.extend_bez_path(font, transform, depth + 1, bez);
+            }
+        }
+    }
+
+    /// The bounding box of the layer including resolved components, or `None`
+    /// if the layer has no outline.
+    pub fn bounds(&self, font: &Font) -> Option<Rect> {
+        let bez = self.to_bez_path(font);
+        if bez.elements().is_empty() {
+            return None;
+        }
+        Some(bez.bounding_box())
+    }
+
+    /// The left sidebearing, or `None` if the layer has no outline.
+    pub fn lsb(&self, font: &Font) -> Option<f64> {
+        self.bounds(font).map(|bounds| bounds.x0)
+    }
+
+    /// The right sidebearing, or `None` if the layer has no outline.
+    pub fn rsb(&self, font: &Font) -> Option<f64> {
+        self.bounds(font).map(|bounds| self.width - bounds.x1)
+    }
+}
+
+impl Font {
+    /// Find the layer of a component's base glyph that corresponds to `layer`.
+    ///
+    /// Layers are matched by ID first, then brace and bracket layers by name
+    /// within the same master, falling back to the master layer itself.
+    pub fn resolve_component(&self, component: &Component, layer: &Layer) -> Option<&Layer> {
+        let base_glyph = self.get_glyph(&component.name)?;
+        base_glyph
+            .get_layer(&layer.layer_id)
+            .or_else(|| {
+                let name = layer.name.as_ref()?;
+                layer.associated_master_id.as_ref()?;
+                base_glyph.layers.iter().find(|base_layer| {
+                    base_layer.associated_master_id == layer.associated_master_id
+                        && base_layer.name.as_ref() == Some(name)
+                })
+            })
+            .or_else(|| base_glyph.get_layer(layer.master_id()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{component, font, glyph, layer, path};
+
+    fn square(x: f64, y: f64, size: f64) -> Path {
+        path(
+            true,
+            &[(x + size, y), (x + size, y + size), (x, y + size), (x, y)],
+        )
+    }
+
+    #[test]
+    fn path_area_and_direction() {
+        let mut path = square(0.0, 0.0, 100.0);
+        assert_eq!(path.area(), 10000.0);
+        assert!(!path.is_clockwise());
+        assert_eq!(path.bbox(), Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+        assert!(path.contains(Point::new(50.0, 50.0)));
+        assert!(!path.contains(Point::new(150.0, 50.0)));
+
+        path.reverse();
+        assert_eq!(path.area(), -10000.0);
+        assert!(path.is_clockwise());
+    }
+
+    #[test]
+    fn path_curve_bbox_is_tight() {
+        let mut path = Path::new(true);
+        path.add((100.0, 0.0), NodeType::OffCurve);
+        path.add((100.0, 100.0), NodeType::OffCurve);
+        path.add((0.0, 100.0), NodeType::Curve);
+        path.add((0.0, 0.0), NodeType::Line);
+        let bbox = path.bbox().unwrap();
+        assert_eq!(bbox.x0, 0.0);
+        assert!((bbox.x1 - 75.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn layer_sidebearings_with_components() {
+        let font = font(vec![
+            glyph(
+                "a",
+                vec![layer(
+                    "m01",
+                    300.0,
+                    Some(vec![square(50.0, 0.0, 200.0)]),
+                    None,
+                )],
+            ),
+            glyph(
+                "b",
+                vec![layer(
+                    "m01",
+                    600.0,
+                    Some(vec![square(20.0, 0.0, 10.0)]),
+                    Some(vec![component("a", Affine::translate((300.0, 0.0)))]),
+                )],
+            ),
+        ]);
+
+        let b = font.get_glyph("b").unwrap().get_layer("m01").unwrap();
+        assert_eq!(b.bounds(&font), Some(Rect::new(20.0, 0.0, 550.0, 200.0)));
+        assert_eq!(b.lsb(&font), Some(20.0));
+        assert_eq!(b.rsb(&font), Some(50.0));
+    }
+}
@@ -0,0 +1,250 @@
+//! PostScript hinting data: alignment zones, stems and blue values.
+
+use crate::font::{AlignmentZone, Font, FontMaster};
+use crate::plist::Plist;
+
+/// The font-wide hinting values of a master, as in the UFO fontinfo.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostscriptHinting {
+    pub blue_values: Vec<f64>,
+    pub other_blues: Vec<f64>,
+    pub family_blues: Vec<f64>,
+    pub family_other_blues: Vec<f64>,
+    pub stem_snap_h: Vec<f64>,
+    pub stem_snap_v: Vec<f64>,
+    pub blue_scale: Option<f64>,
+    pub blue_shift: Option<f64>,
+    pub blue_fuzz: Option<f64>,
+}
+
+impl AlignmentZone {
+    /// Whether the zone goes into the BlueValues: the baseline zone and all
+    /// zones overshooting upwards. All others are OtherBlues.
+    pub fn is_blue_value(&self) -> bool {
+        self.position == 0.0 || self.size >= 0.0
+    }
+
+    /// The zone as a (bottom, top) pair of blue values.
+    pub fn blues(&self) -> (f64, f64) {
+        let end = self.position + self.size;
+        (self.position.min(end), self.position.max(end))
+    }
+}
+
+impl PostscriptHinting {
+    /// The alignment zones the blue values describe. Zones flat at the top
+    /// are taken to overshoot downwards, like the baseline and descender
+    /// zones do.
+    pub fn alignment_zones(&self) -> Vec<AlignmentZone> {
+        let blue_values = self.blue_values.chunks_exact(2).map(|pair| {
+            if pair[1] == 0.0 {
+                AlignmentZone {
+                    position: 0.0,
+                    size: pair[0],
+                }
+            } else {
+                AlignmentZone {
+                    position: pair[0],
+                    size: pair[1] - pair[0],
+                }
+            }
+        });
+        let other_blues = self.other_blues.chunks_exact(2).map(|pair| AlignmentZone {
+            position: pair[1],
+            size: pair[0] - pair[1],
+        });
+        blue_values.chain(other_blues).collect()
+    }
+}
+
+impl Font {
+    /// The alignment zones of `master`: stored in Glyphs 2, the metrics with
+    /// an overshoot in Glyphs 3. Sorted by position.
+    pub fn alignment_zones(&self, master: &FontMaster) -> Vec<AlignmentZone> {
+        let mut zones = match &master.alignment_zones {
+            Some(zones) => zones.clone(),
+            None => master
+                .metric_values
+                .iter()
+                .flatten()
+                .filter_map(|metric| {
+                    let size = metric.over?;
+                    let position = metric.pos.unwrap_or(0.0);
+                    (size != 0.0).then_some(AlignmentZone { position, size })
+                })
+                .collect(),
+        };
+        zones.sort_by(|a, b| a.position.total_cmp(&b.position));
+        zones
+    }
+
+    /// The horizontal and vertical stems of `master`.
+    pub fn master_stems(&self, master: &FontMaster) -> (Vec<f64>, Vec<f64>) {
+        match (&self.stems, &master.stem_values) {
+            (Some(stems), Some(values)) => {
+                let mut horizontal = Vec::new();
+                let mut vertical = Vec::new();
+                for (stem, &value) in stems.iter().zip(values) {
+                    if stem.horizontal.unwrap_or(false) {
+                        horizontal.push(value);
+                    } else {
+                        vertical.push(value);
+                    }
+                }
+                (horizontal, vertical)
+            }
+            _ => (
+                master.horizontal_stems.clone().unwrap_or_default(),
+                master.vertical_stems.clone().unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// The hinting values of `master`. The "blueValues" and "otherBlues"
+    /// custom parameters override the alignment zones, and the other blue
+    /// parameters are looked up on the master first, then on the font.
+    pub fn postscript_hinting(&self, master: &FontMaster) -> PostscriptHinting {
+        let parameter = |name: &str| {
+            master
+                .custom_parameter(name)
+                .or_else(|| self.custom_parameter(name))
+        };
+        let numbers = |name: &str| -> Option<Vec<f64>> {
+            parameter(name)?
+                .as_array()?
+                .iter()
+                .map(Plist::as_f64)
+                .collect()
+        };
+
+        let mut blue_values = Vec::new();
+        let mut other_blues = Vec::new();
+        for zone in self.alignment_zones(master) {
+            let (bottom, top) = zone.blues();
+            let blues = if zone.is_blue_value() {
+                &mut blue_values
+            } else {
+                &mut other_blues
+            };
+            blues.extend([bottom, top]);
+        }
+        let (stem_snap_h, stem_snap_v) = self.master_stems(master);
+
+        PostscriptHinting {
+            blue_values: numbers("blueValues").unwrap_or(blue_values),
+            other_blues: numbers("otherBlues").unwrap_or(other_blues),
+            family_blues: numbers("familyBlues").unwrap_or_default(),
+            family_other_blues: numbers("familyOtherBlues").unwrap_or_default(),
+            stem_snap_h,
+            stem_snap_v,
+            blue_scale: parameter("blueScale").and_then(Plist::as_f64),
+            blue_shift: parameter("blueShift").and_then(Plist::as_f64),
+            blue_fuzz: parameter("blueFuzz").and_then(Plist::as_f64),
+        }
+    }
+}
+
+impl FontMaster {
+    /// Store `hinting` in the master, the Glyphs 2 way: blue values as
+    /// alignment zones, stems per direction, everything else as custom
+    /// parameters.
+    pub fn set_postscript_hinting(&mut self, hinting: &PostscriptHinting) {
+        let non_empty = |values: &Vec<f64>| (!values.is_empty()).then(|| values.clone());
+        let zones = hinting.alignment_zones();
+        self.alignment_zones = (!zones.is_empty()).then_some(zones);
+        self.horizontal_stems = non_empty(&hinting.stem_snap_h);
+        self.vertical_stems = non_empty(&hinting.stem_snap_v);
+        self.stem_values = None;
+
+        for (name, values) in [
+            ("familyBlues", &hinting.family_blues),
+            ("familyOtherBlues", &hinting.family_other_blues),
+        ] {
+            if let Some(values) = non_empty(values) {
+                let values = values.into_iter().map(Plist::from).collect::<Vec<_>>();
+                self.set_custom_parameter(name, values.into());
+            }
+        }
+        for (name, value) in [
+            ("blueScale", hinting.blue_scale),
+            ("blueShift", hinting.blue_shift),
+            ("blueFuzz", hinting.blue_fuzz),
+        ] {
+            if let Some(value) = value {
+                self.set_custom_parameter(name, value.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    #[test]
+    fn zones_and_stems_roundtrip() {
+        let font: Font = FromPlist::from_plist(
+            Plist::parse(
+                r#"{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                fontMaster = ({
+                    id = m01;
+                    alignmentZones = ("{800, 16}", "{500, 12}", "{0, -16}", "{-200, -16}");
+                    horizontalStems = (40, 42);
+                    verticalStems = (90);
+                    customParameters = ({name = blueScale; value = 0.039625;});
+                });
+            }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let master = &font.font_master[0];
+        let hinting = font.postscript_hinting(master);
+        assert_eq!(
+            hinting.blue_values,
+            vec![-16.0, 0.0, 500.0, 512.0, 800.0, 816.0]
+        );
+        assert_eq!(hinting.other_blues, vec![-216.0, -200.0]);
+        assert_eq!(hinting.stem_snap_h, vec![40.0, 42.0]);
+        assert_eq!(hinting.stem_snap_v, vec![90.0]);
+        assert_eq!(hinting.blue_scale, Some(0.039625));
+
+        let mut zones = hinting.alignment_zones();
+        zones.sort_by(|a, b| a.position.total_cmp(&b.position));
+        assert_eq!(zones, font.alignment_zones(master));
+    }
+
+    #[test]
+    fn glyphs3_zones_and_stems() {
+        let font: Font = FromPlist::from_plist(
+            Plist::parse(
+                r#"{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                stems = ({horizontal = 1; name = hStem0;}, {name = vStem0;});
+                fontMaster = ({
+                    id = m01;
+                    metricValues = ({over = 16; pos = 800;}, {over = -16;}, {pos = 700;});
+                    stemValues = (40, 90);
+                });
+            }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let hinting = font.postscript_hinting(&font.font_master[0]);
+        assert_eq!(hinting.blue_values, vec![-16.0, 0.0, 800.0, 816.0]);
+        assert!(hinting.other_blues.is_empty());
+        assert_eq!(hinting.stem_snap_h, vec![40.0]);
+        assert_eq!(hinting.stem_snap_v, vec![90.0]);
+    }
+}
@@ -0,0 +1,400 @@
+//! Interpolating instances: where masters, brace and bracket layers are in
+//! the design space, and the glyphs, metrics and kerning at any location.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::font::{Font, FontMaster, Glyph, Instance, Layer};
+use crate::interpolation::VariationModel;
+use crate::metrics::MasterMetrics;
+use crate::plist::Plist;
+
+/// Kerning values by first and second glyph name or group key.
+pub type Kerning = BTreeMap<String, BTreeMap<String, f64>>;
+
+impl Instance {
+    /// The design location of the instance on the axis at `index`, like
+    /// [`FontMaster::axis_value`].
+    pub fn axis_value(&self, index: usize) -> f64 {
+        if let Some(values) = &self.axes_values {
+            return values.get(index).copied().unwrap_or(0.0);
+        }
+        let (value, default) = match index {
+            0 => (self.interpolation_weight, 100.0),
+            1 => (self.interpolation_width, 100.0),
+            2 => (self.interpolation_custom, 0.0),
+            3 => (self.interpolation_custom1, 0.0),
+            4 => (self.interpolation_custom2, 0.0),
+            5 => (self.interpolation_custom3, 0.0),
+            _ => (None, 0.0),
+        };
+        value.unwrap_or(default)
+    }
+
+    /// The family name of the instance: its "familyName" custom parameter,
+    /// or the family name of `font`.
+    pub fn family_name<'a>(&'a self, font: &'a Font) -> &'a str {
+        self.custom_parameter("familyName")
+            .and_then(Plist::as_str)
+            .unwrap_or(&font.family_name)
+    }
+
+    /// Whether the instance is exported, which is the default.
+    pub fn is_exported(&self) -> bool {
+        self.other_stuff.get("exports").and_then(Plist::as_i64) != Some(0)
+    }
+}
+
+impl Layer {
+    /// The design location of a brace layer: "{100, 50}" in the layer name
+    /// in Glyphs 2, the `coordinates` attribute in Glyphs 3.
+    pub fn brace_coordinates(&self) -> Option<Vec<f64>> {
+        if let Some(coordinates) = self.attribute("coordinates") {
+            return coordinates.as_array()?.iter().map(Plist::as_f64).collect();
+        }
+        let name = self.name.as_deref()?;
+        let start = name.find('{')?;
+        let end = start + name[start..].find('}')?;
+        name[start + 1..end]
+            .split(',')
+            .map(|value| value.trim().parse().ok())
+            .collect()
+    }
+
+    /// The (minimum, maximum) design locations per axis a bracket layer
+    /// replaces its master layer in. The minimum is inclusive, the maximum
+    /// exclusive. Glyphs 2 names them "Name [100]", or "Name ]100]" for
+    /// below 100, on the first axis; Glyphs 3 has the `axisRules` attribute.
+    pub fn bracket_ranges(&self) -> Option<Vec<(Option<f64>, Option<f64>)>> {
+        if let Some(rules) = self.attribute("axisRules") {
+            return Some(
+                rules
+                    .as_array()?
+                    .iter()
+                    .map(|rule| {
+                        (
+                            rule.get("min").and_then(Plist::as_f64),
+                            rule.get("max").and_then(Plist::as_f64),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        let name = self.name.as_deref()?;
+        let end = name.rfind(']')?;
+        let start = name[..end].rfind(['[', ']'])?;
+        let value: f64 = name[start + 1..end].trim().parse().ok()?;
+        if name[start..].starts_with('[') {
+            Some(vec![(Some(value), None)])
+        } else {
+            Some(vec![(None, Some(value))])
+        }
+    }
+
+    /// Whether a bracket layer replaces its master layer at `location`.
+    fn bracket_applies(&self, location: &[f64]) -> bool {
+        let Some(ranges) = self.bracket_ranges() else {
+            return false;
+        };
+        ranges
+            .iter()
+            .zip(location)
+            .all(|(&(minimum, maximum), &value)| {
+                !matches!(minimum, Some(minimum) if value < minimum)
+                    && !matches!(maximum, Some(maximum) if value >= maximum)
+            })
+    }
+
+    fn attribute(&self, key: &str) -> Option<&Plist> {
+        self.other_stuff.get("attr")?.get(key)
+    }
+}
+
+impl Font {
+    /// The number of axes: those defined, or the three of Glyphs 2 fonts
+    /// without an "Axes" custom parameter.
+    pub fn axis_count(&self) -> usize {
+        match self.font_axes().len() {
+            0 => 3,
+            count => count,
+        }
+    }
+
+    /// The design location of `master`.
+    pub fn master_location(&self, master: &FontMaster) -> Vec<f64> {
+        (0..self.axis_count())
+            .map(|i| master.axis_value(i))
+            .collect()
+    }
+
+    /// The design location of `instance`.
+    pub fn instance_location(&self, instance: &Instance) -> Vec<f64> {
+        (0..self.axis_count())
+            .map(|i| instance.axis_value(i))
+            .collect()
+    }
+
+    /// Normalize a design location against the masters: the default master
+    /// is at the origin, the extremes of the masters at -1 and 1. Locations
+    /// outside the masters are not clamped.
+    pub fn normalize_location(&self, location: &[f64]) -> Vec<f64> {
+        let masters: Vec<Vec<f64>> = self
+            .font_master
+            .iter()
+            .map(|master| self.master_location(master))
+            .collect();
+        let default = self
+            .default_master()
+            .map(|master| self.master_location(master))
+            .unwrap_or_default();
+        location
+            .iter()
+            .enumerate()
+            .map(|(axis, &value)| {
+                let default = default.get(axis).copied().unwrap_or(value);
+                let values = masters.iter().filter_map(|m| m.get(axis).copied());
+                let minimum = values.clone().fold(default, f64::min);
+                let maximum = values.fold(default, f64::max);
+                if value < default && minimum < default {
+                    (value - default) / (default - minimum)
+                } else if value > default && maximum > default {
+                    (value - default) / (maximum - default)
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// The weight of each master at the design `location`.
+    pub fn master_weights(&self, location: &[f64]) -> Result<Vec<f64>, String> {
+        let locations: Vec<Vec<f64>> = self
+            .font_master
+            .iter()
+            .map(|master| self.normalize_location(&self.master_location(master)))
+            .collect();
+        let model = VariationModel::new(&locations)?;
+        Ok(model.weights(&self.normalize_location(location)))
+    }
+
+    /// The layers of `glyph` that make up its instance at the design
+    /// `location`, with their design locations: the master layers, or the
+    /// bracket layers replacing them there, and the brace layers.
+    pub fn instance_sources<'a>(
+        &self,
+        glyph: &'a Glyph,
+        location: &[f64],
+    ) -> Vec<(&'a Layer, Vec<f64>)> {
+        let mut sources = Vec::new();
+        for master in &self.font_master {
+            let bracket_layer = glyph.layers.iter().find(|layer| {
+                layer.associated_master_id.as_ref() == Some(&master.id)
+                    && layer.bracket_applies(location)
+            });
+            if let Some(layer) = bracket_layer.or_else(|| glyph.get_layer(&master.id)) {
+                sources.push((layer, self.master_location(master)));
+            }
+        }
+        for layer in &glyph.layers {
+            if layer.associated_master_id.is_none() || layer.bracket_ranges().is_some() {
+                continue;
+            }
+            if let Some(mut coordinates) = layer.brace_coordinates() {
+                coordinates.resize(self.axis_count(), 0.0);
+                sources.push((layer, coordinates));
+            }
+        }
+        sources
+    }
+
+    /// Interpolate `glyph` at the design `location`, with brace layers as
+    /// intermediate masters and bracket layers applied. Smart components
+    /// are decomposed first.
+    pub fn instantiate_glyph(&self, glyph: &Glyph, location: &[f64]) -> Result<Layer, String> {
+        let sources = self.instance_sources(glyph, location);
+        let layers: Vec<Cow<Layer>> = sources
+            .iter()
+            .map(|(layer, _)| {
+                Ok(match self.decompose_smart_components(layer)? {
+                    Some(decomposed) => Cow::Owned(decomposed),
+                    None => Cow::Borrowed(*layer),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        let locations: Vec<Vec<f64>> = sources
+            .iter()
+            .map(|(_, location)| self.normalize_location(location))
+            .collect();
+        let model = VariationModel::new(&locations)
+            .map_err(|e| format!("glyph '{}': {}", glyph.glyphname, e))?;
+        let layers: Vec<&Layer> = layers.iter().map(|layer| layer.as_ref()).collect();
+        Layer::interpolate(&layers, &model.weights(&self.normalize_location(location)))
+            .map_err(|e| format!("glyph '{}': {}", glyph.glyphname, e))
+    }
+
+    /// The master metrics at the design `location`. Values missing from any
+    /// master are taken from the default master.
+    pub fn instance_metrics(&self, location: &[f64]) -> Result<MasterMetrics, String> {
+        let weights = self.master_weights(location)?;
+        let metrics: Vec<MasterMetrics> = self
+            .font_master
+            .iter()
+            .map(|master| self.master_metrics(master))
+            .collect();
+        let default = self
+            .default_master()
+            .map(|master| self.master_metrics(master))
+            .unwrap_or_default();
+        let interpolate = |value: fn(&MasterMetrics) -> Option<f64>| {
+            metrics
+                .iter()
+                .zip(&weights)
+                .map(|(m, w)| Some(value(m)? * w))
+                .sum::<Option<f64>>()
+                .or_else(|| value(&default))
+        };
+        let parameters = default
+            .parameters
+            .iter()
+            .map(|&(name, default_value)| {
+                let value = metrics
+                    .iter()
+                    .zip(&weights)
+                    .map(|(m, w)| {
+                        let (_, value) = m.parameters.iter().find(|(n, _)| *n == name)?;
+                        Some(value * w)
+                    })
+                    .sum::<Option<f64>>();
+                (name, value.unwrap_or(default_value))
+            })
+            .collect();
+        Ok(MasterMetrics {
+            ascender: interpolate(|m| m.ascender),
+            cap_height: interpolate(|m| m.cap_height),
+            x_height: interpolate(|m| m.x_height),
+            descender: interpolate(|m| m.descender),
+            italic_angle: interpolate(|m| m.italic_angle),
+            parameters,
+        })
+    }
+
+    /// The kerning of `master`, from `kerning` in Glyphs 2 and `kerningLTR`
+    /// in Glyphs 3. Group keys keep their "@MMK_L_" and "@MMK_R_" prefixes.
+    pub fn master_kerning(&self, master: &FontMaster) -> Kerning {
+        let Some(kerning) = self
+            .other_stuff
+            .get("kerningLTR")
+            .or_else(|| self.other_stuff.get("kerning"))
+            .and_then(|kerning| kerning.get(&master.id))
+            .and_then(Plist::as_dict)
+        else {
+            return Kerning::new();
+        };
+        kerning
+            .iter()
+            .filter_map(|(first, seconds)| {
+                let seconds = seconds
+                    .as_dict()?
+                    .iter()
+                    .filter_map(|(second, value)| Some((second.clone(), value.as_f64()?)))
+                    .collect();
+                Some((first.clone(), seconds))
+            })
+            .collect()
+    }
+
+    /// The kerning at the design `location`. Pairs missing from a master
+    /// count as zero there.
+    pub fn instance_kerning(&self, location: &[f64]) -> Result<Kerning, String> {
+        let weights = self.master_weights(location)?;
+        let mut kerning = Kerning::new();
+        for (master, weight) in self.font_master.iter().zip(weights) {
+            for (first, seconds) in self.master_kerning(master) {
+                let row = kerning.entry(first).or_default();
+                for (second, value) in seconds {
+                    *row.entry(second).or_default() += value * weight;
+                }
+            }
+        }
+        Ok(kerning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{glyph, path};
+    use crate::from_plist::FromPlist;
+
+    fn font() -> Font {
+        FromPlist::from_plist(
+            Plist::parse(
+                r#"{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                kerning = {m01 = {"@MMK_L_A" = {V = -80;};}; m02 = {"@MMK_L_A" = {V = -40; W = -20;};};};
+                fontMaster = ({id = m01; weightValue = 100; ascender = 700;}, {id = m02; weightValue = 200; ascender = 800;});
+            }"#,
+            )
+            .unwrap(),
+        ).unwrap()
+    }
+
+    fn layer(layer_id: &str, name: Option<&str>, width: f64) -> Layer {
+        Layer {
+            layer_id: layer_id.into(),
+            associated_master_id: name.map(|_| "m01".to_string()),
+            name: name.map(String::from),
+            width,
+            paths: Some(vec![path(
+                true,
+                &[(0.0, 0.0), (width, 0.0), (width, 100.0)],
+            )]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn instantiate_with_brace_and_bracket_layers() {
+        let font = font();
+        let glyph = glyph(
+            "a",
+            vec![
+                layer("m01", None, 100.0),
+                layer("m02", None, 300.0),
+                layer("brace", Some("{150}"), 100.0),
+                layer("bracket", Some("Alt ]120]"), 50.0),
+            ],
+        );
+
+        let brace = font
+            .instantiate_glyph(&glyph, &[150.0, 100.0, 0.0])
+            .unwrap();
+        assert_eq!(brace.width, 100.0);
+        let between = font
+            .instantiate_glyph(&glyph, &[175.0, 100.0, 0.0])
+            .unwrap();
+        assert_eq!(between.width, 200.0);
+        let bracket = font
+            .instantiate_glyph(&glyph, &[100.0, 100.0, 0.0])
+            .unwrap();
+        assert_eq!(bracket.width, 50.0);
+        assert_eq!(bracket.paths.unwrap()[0].nodes[1].pt.x, 50.0);
+    }
+
+    #[test]
+    fn instance_metrics_and_kerning() {
+        let font = font();
+        let location = [150.0, 100.0, 0.0];
+        assert_eq!(
+            font.instance_metrics(&location).unwrap().ascender,
+            Some(750.0)
+        );
+        let kerning = font.instance_kerning(&location).unwrap();
+        assert_eq!(kerning["@MMK_L_A"]["V"], -60.0);
+        assert_eq!(kerning["@MMK_L_A"]["W"], -10.0);
+    }
+}
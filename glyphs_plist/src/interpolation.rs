@@ -0,0 +1,409 @@
+//! Interpolating between compatible layers.
+//!
+//! The variation model follows the one fontTools uses for variable fonts, so
+//! sparse masters and intermediate layers get the same regions of influence
+//! as in compiled fonts.
+
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point, Vec2};
+
+use crate::font::{Layer, NodeType, Path};
+
+/// The region of influence of a master on one axis: (lower, peak, upper).
+type Support = HashMap<usize, (f64, f64, f64)>;
+
+/// A model over masters at normalized locations, answering how much each
+/// master contributes at any location.
+#[derive(Clone, Debug)]
+pub struct VariationModel {
+    /// Master indices, sorted so that the default master comes first and
+    /// masters with fewer non-zero axes before those with more.
+    order: Vec<usize>,
+    supports: Vec<Support>,
+    /// For each sorted master, its delta expressed as weights of the
+    /// (original) master values.
+    delta_weights: Vec<Vec<f64>>,
+}
+
+impl VariationModel {
+    /// Build a model from normalized master locations, with the default at
+    /// the origin. All locations must have the same number of axes.
+    pub fn new(locations: &[Vec<f64>]) -> Result<Self, String> {
+        let axis_count = locations.first().map(Vec::len).unwrap_or_default();
+        if locations.iter().any(|loc| loc.len() != axis_count) {
+            return Err("master locations have differing numbers of axes".into());
+        }
+        if !locations.iter().any(|loc| loc.iter().all(|v| *v == 0.0)) {
+            return Err("no master at the default location".into());
+        }
+        for (i, loc) in locations.iter().enumerate() {
+            if locations[..i].contains(loc) {
+                return Err(format!("duplicate master location {:?}", loc));
+            }
+        }
+
+        let mut order: Vec<usize> = (0..locations.len()).collect();
+        order.sort_by(|&a, &b| {
+            let rank = |loc: &[f64]| {
+                (
+                    loc.iter().filter(|v| **v != 0.0).count(),
+                    loc.iter().map(|v| v.abs()).sum::<f64>(),
+                )
+            };
+            rank(&locations[a])
+                .partial_cmp(&rank(&locations[b]))
+                .unwrap()
+        });
+
+        let axis_min: Vec<f64> = (0..axis_count)
+            .map(|axis| locations.iter().map(|loc| loc[axis]).fold(0.0, f64::min))
+            .collect();
+        let axis_max: Vec<f64> = (0..axis_count)
+            .map(|axis| locations.iter().map(|loc| loc[axis]).fold(0.0, f64::max))
+            .collect();
+
+        let mut supports: Vec<Support> = Vec::with_capacity(order.len());
+        for (i, &master) in order.iter().enumerate() {
+            let loc = &locations[master];
+            let mut region: Support = loc
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v != 0.0)
+                .map(|(axis, &v)| {
+                    if v > 0.0 {
+                        (axis, (0.0, v, axis_max[axis]))
+                    } else {
+                        (axis, (axis_min[axis], v, 0.0))
+                    }
+                })
+                .collect();
+
+            // Shrink the region so it does not overlap the peaks of earlier
+            // masters that lie within it.
+            for &previous in &order[..i] {
+                let other = &locations[previous];
+                let other_axes: Vec<usize> = (0..axis_count).filter(|a| other[*a] != 0.0).collect();
+                if !other_axes.iter().all(|axis| region.contains_key(axis)) {
+                    continue;
+                }
+                let relevant = region.iter().all(|(axis, &(lower, peak, upper))| {
+                    let v = other[*axis];
+                    v == peak || (lower < v && v < upper)
+                });
+                if !relevant {
+                    continue;
+                }
+
+                let mut best_ratio = -1.0;
+                let mut best_axes: Vec<(usize, (f64, f64, f64))> = Vec::new();
+                for axis in other_axes {
+                    let v = other[axis];
+                    let (lower, peak, upper) = region[&axis];
+                    let (new_lower, new_upper, ratio) = if v < peak {
+                        (v, upper, (v - peak) / (lower - peak))
+                    } else if peak < v {
+                        (lower, v, (v - peak) / (upper - peak))
+                    } else {
+                        continue;
+                    };
+                    if ratio > best_ratio {
+                        best_ratio = ratio;
+                        best_axes.clear();
+                    }
+                    if ratio == best_ratio {
+                        best_axes.push((axis, (new_lower, peak, new_upper)));
+                    }
+                }
+                region.extend(best_axes);
+            }
+            supports.push(region);
+        }
+
+        let mut delta_weights: Vec<Vec<f64>> = Vec::with_capacity(order.len());
+        for (i, &master) in order.iter().enumerate() {
+            let mut weights = vec![0.0; locations.len()];
+            weights[master] = 1.0;
+            for j in 0..i {
+                let scalar = support_scalar(&locations[master], &supports[j]);
+                if scalar != 0.0 {
+                    for (weight, delta_weight) in weights.iter_mut().zip(&delta_weights[j]) {
+                        *weight -= scalar * delta_weight;
+                    }
+                }
+            }
+            delta_weights.push(weights);
+        }
+
+        Ok(Self {
+            order,
+            supports,
+            delta_weights,
+        })
+    }
+
+    /// The weight of each master (in the order they were given) at the
+    /// normalized `location`. The weights sum to one.
+    pub fn weights(&self, location: &[f64]) -> Vec<f64> {
+        let mut weights = vec![0.0; self.order.len()];
+        for (support, delta_weights) in self.supports.iter().zip(&self.delta_weights) {
+            let scalar = support_scalar(location, support);
+            if scalar != 0.0 {
+                for (weight, delta_weight) in weights.iter_mut().zip(delta_weights) {
+                    *weight += scalar * delta_weight;
+                }
+            }
+        }
+        weights
+    }
+}
+
+fn support_scalar(location: &[f64], support: &Support) -> f64 {
+    let mut scalar = 1.0;
+    for (&axis, &(lower, peak, upper)) in support {
+        let v = location.get(axis).copied().unwrap_or_default();
+        if peak == 0.0 || v == peak {
+            continue;
+        }
+        if v <= lower || upper <= v {
+            return 0.0;
+        }
+        scalar *= if v < peak {
+            (v - lower) / (peak - lower)
+        } else {
+            (v - upper) / (peak - upper)
+        };
+    }
+    scalar
+}
+
+impl Layer {
+    /// Check that `self` and `other` can be interpolated: same paths with the
+    /// same node structure, same components and the same anchors.
+    pub fn check_compatible(&self, other: &Layer) -> Result<(), String> {
+        let paths = self.paths.as_deref().unwrap_or_default();
+        let other_paths = other.paths.as_deref().unwrap_or_default();
+        if paths.len() != other_paths.len() {
+            return Err(format!(
+                "different number of paths ({} vs. {})",
+                paths.len(),
+                other_paths.len()
+            ));
+        }
+        // Paths that would match in another order were likely reordered.
+        let hint = if path_signatures(paths) == path_signatures(other_paths) {
+            ", the paths may be in a different order"
+        } else {
+            ""
+        };
+        for (i, (path, other_path)) in paths.iter().zip(other_paths).enumerate() {
+            let first_difference = path
+                .nodes
+                .iter()
+                .zip(&other_path.nodes)
+                .position(|(node, other_node)| !node.node_type.is_compatible(other_node.node_type));
+            if let Some(j) = first_difference {
+                let (node, other_node) = (&path.nodes[j], &other_path.nodes[j]);
+                return Err(format!(
+                    "path {} node {} at ({}, {}) has different types ({:?} vs. {:?}){}",
+                    i, j, node.pt.x, node.pt.y, node.node_type, other_node.node_type, hint
+                ));
+            }
+            if path.nodes.len() != other_path.nodes.len() {
+                return Err(format!(
+                    "path {} has a different number of nodes ({} vs. {}), the first {} match{}",
+                    i,
+                    path.nodes.len(),
+                    other_path.nodes.len(),
+                    path.nodes.len().min(other_path.nodes.len()),
+                    hint
+                ));
+            }
+        }
+
+        let components = self.components.as_deref().unwrap_or_default();
+        let other_components = other.components.as_deref().unwrap_or_default();
+        let names: Vec<_> = components.iter().map(|c| &c.name).collect();
+        let other_names: Vec<_> = other_components.iter().map(|c| &c.name).collect();
+        if names != other_names {
+            let mut sorted_names = names.clone();
+            let mut sorted_other_names = other_names.clone();
+            sorted_names.sort();
+            sorted_other_names.sort();
+            let difference = if sorted_names == sorted_other_names {
+                "components in a different order"
+            } else {
+                "different components"
+            };
+            return Err(format!(
+                "{} ({:?} vs. {:?})",
+                difference, names, other_names
+            ));
+        }
+
+        let mut anchors: Vec<_> = self.anchors.iter().flatten().map(|a| &a.name).collect();
+        let mut other_anchors: Vec<_> = other.anchors.iter().flatten().map(|a| &a.name).collect();
+        anchors.sort();
+        other_anchors.sort();
+        if anchors != other_anchors {
+            return Err(format!(
+                "different anchors ({:?} vs. {:?})",
+                anchors, other_anchors
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Compute the weighted sum of compatible layers, e.g. with weights from
+    /// [`VariationModel::weights`]. Everything but the geometry and width is
+    /// taken from the first layer.
+    pub fn interpolate(layers: &[&Layer], weights: &[f64]) -> Result<Layer, String> {
+        let (first, rest) = layers.split_first().ok_or("no layers to interpolate")?;
+        if layers.len() != weights.len() {
+            return Err("number of layers and weights differ".into());
+        }
+        for layer in rest {
+            first
+                .check_compatible(layer)
+                .map_err(|e| format!("layer {} is incompatible: {}", layer.layer_id, e))?;
+        }
+
+        let mut result = (*first).clone();
+        result.width = layers.iter().zip(weights).map(|(l, w)| l.width * w).sum();
+
+        for (i, path) in result.paths.iter_mut().flatten().enumerate() {
+            for (j, node) in path.nodes.iter_mut().enumerate() {
+                node.pt = weighted_point(layers, weights, |l| {
+                    l.paths.as_ref().unwrap()[i].nodes[j].pt
+                });
+            }
+        }
+
+        for (i, component) in result.components.iter_mut().flatten().enumerate() {
+            let mut coeffs = [0.0; 6];
+            for (layer, weight) in layers.iter().zip(weights) {
+                let transform = layer.components.as_ref().unwrap()[i]
+                    .transform
+                    .unwrap_or_default();
+                for (c, v) in coeffs.iter_mut().zip(transform.as_coeffs()) {
+                    *c += v * weight;
+                }
+            }
+            let transform = Affine::new(coeffs);
+            component.transform = (component.transform.is_some() || transform != Affine::IDENTITY)
+                .then_some(transform);
+            if let Some(piece) = component.piece.as_mut() {
+                for (axis, value) in piece.iter_mut() {
+                    let own = *value;
+                    *value = layers
+                        .iter()
+                        .zip(weights)
+                        .map(|(layer, weight)| {
+                            let other = &layer.components.as_ref().unwrap()[i];
+                            let other = other.piece.as_ref().and_then(|p| p.get(axis));
+                            other.copied().unwrap_or(own) * weight
+                        })
+                        .sum();
+                }
+            }
+        }
+
+        for anchor in result.anchors.iter_mut().flatten() {
+            anchor.position = weighted_point(layers, weights, |l| {
+                l.anchors
+                    .iter()
+                    .flatten()
+                    .find(|a| a.name == anchor.name)
+                    .unwrap()
+                    .position
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+/// The node structure of each path, as the compatible classes of its node
+/// types, sorted, for telling reordered paths from different ones.
+fn path_signatures(paths: &[Path]) -> Vec<Vec<u8>> {
+    let mut signatures: Vec<Vec<u8>> = paths
+        .iter()
+        .map(|path| {
+            path.nodes
+                .iter()
+                .map(|node| match node.node_type {
+                    NodeType::Line | NodeType::LineSmooth => 0,
+                    NodeType::OffCurve => 1,
+                    NodeType::Curve | NodeType::CurveSmooth => 2,
+                    NodeType::QCurve | NodeType::QCurveSmooth => 3,
+                })
+                .collect()
+        })
+        .collect();
+    signatures.sort();
+    signatures
+}
+
+fn weighted_point(layers: &[&Layer], weights: &[f64], point: impl Fn(&Layer) -> Point) -> Point {
+    let sum: Vec2 = layers
+        .iter()
+        .zip(weights)
+        .map(|(layer, weight)| point(layer).to_vec2() * *weight)
+        .fold(Vec2::ZERO, |acc, v| acc + v);
+    sum.to_point()
+}
+
+impl NodeType {
+    /// Whether nodes of the two types can be interpolated with each other,
+    /// i.e. both are on-curve of the same kind or both are off-curve.
+    pub fn is_compatible(self, other: NodeType) -> bool {
+        use NodeType::*;
+        matches!(
+            (self, other),
+            (Line | LineSmooth, Line | LineSmooth)
+                | (OffCurve, OffCurve)
+                | (Curve | CurveSmooth, Curve | CurveSmooth)
+                | (QCurve | QCurveSmooth, QCurve | QCurveSmooth)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_weights(model: &VariationModel, location: &[f64], expected: &[f64]) {
+        let weights = model.weights(location);
+        for (w, e) in weights.iter().zip(expected) {
+            assert!((w - e).abs() < 1e-9, "{:?} != {:?}", weights, expected);
+        }
+    }
+
+    #[test]
+    fn model_two_axes() {
+        let model = VariationModel::new(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ])
+        .unwrap();
+        assert_weights(&model, &[0.0, 0.0], &[1.0, 0.0, 0.0, 0.0]);
+        assert_weights(&model, &[0.5, 0.0], &[0.5, 0.5, 0.0, 0.0]);
+        assert_weights(&model, &[0.5, 0.5], &[0.25, 0.25, 0.25, 0.25]);
+        assert_weights(&model, &[1.0, 1.0], &[0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn model_intermediate_master() {
+        let model = VariationModel::new(&[vec![0.0], vec![1.0], vec![0.5]]).unwrap();
+        assert_weights(&model, &[0.25], &[0.5, 0.0, 0.5]);
+        assert_weights(&model, &[0.75], &[0.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn model_requires_default() {
+        assert!(VariationModel::new(&[vec![1.0], vec![0.5]]).is_err());
+    }
+}
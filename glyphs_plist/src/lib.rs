@@ -1,12 +1,38 @@
 //! Lightweight library for reading and writing Glyphs font files.
 
+mod axes;
+mod compatibility;
+mod components;
+mod corner_components;
+#[cfg(test)]
+mod fixtures;
 mod font;
 mod from_plist;
+mod geometry;
+mod hinting;
+mod instances;
+mod interpolation;
+mod lint;
+mod metrics;
+mod names;
 mod norad_interop;
 mod plist;
+mod smart_components;
 mod to_plist;
 
-pub use font::{Anchor, Component, Font, FontMaster, Glyph, Instance, Layer, Node, NodeType, Path};
+pub use axes::AxisDefinition;
+pub use compatibility::Incompatibility;
+pub use font::{
+    AlignmentZone, Anchor, Axis, Background, BackgroundImage, Component, Font, FontMaster, Glyph,
+    Hint, HintScale, HintTarget, HintType, Instance, Layer, LocalizedValue, Metric, MetricValue,
+    Node, NodeIndex, NodeType, PartProperty, Path, Property, Stem,
+};
 pub use from_plist::FromPlist;
+pub use hinting::PostscriptHinting;
+pub use instances::Kerning;
+pub use interpolation::VariationModel;
+pub use lint::{lint_glyph_names, LintIssue, DISABLED_LINT_RULES_KEY, LINT_RULES};
+pub use metrics::{MasterMetrics, VerticalMetrics, METRIC_PARAMETERS};
+pub use names::{name_id, property_key, Language, DEFAULT_LANGUAGE, LANGUAGES};
 pub use plist::Plist;
 pub use to_plist::ToPlist;
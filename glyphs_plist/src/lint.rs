@@ -0,0 +1,356 @@
+//! Linting fonts for problems that Glyphs.app lets through but that break
+//! or spoil exported fonts.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::font::{Font, Glyph, Layer};
+use crate::plist::Plist;
+
+/// The lint rules, by ID, with a description of what they find.
+pub const LINT_RULES: &[(&str, &str)] = &[
+    (
+        "duplicate-unicode",
+        "a codepoint is assigned to more than one glyph",
+    ),
+    (
+        "missing-component",
+        "a component references a glyph that does not exist",
+    ),
+    ("self-reference", "a component references its own glyph"),
+    (
+        "empty-glyph-with-unicode",
+        "a glyph with codepoints has no outlines",
+    ),
+    ("open-path", "an exporting glyph has open paths"),
+    ("unnamed-anchor", "an anchor has no name"),
+    ("off-grid", "coordinates are not on the font's grid"),
+    ("invalid-glyph-name", "a glyph name is not valid in UFOs"),
+    (
+        "missing-glyph-order-glyph",
+        "the glyphOrder lists a glyph that does not exist",
+    ),
+];
+
+/// The font user data key listing the rule IDs disabled for a project.
+pub const DISABLED_LINT_RULES_KEY: &str = "com.daltonmaag.glyphsExchange.disabledLintRules";
+
+/// A problem found by a lint rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintIssue {
+    /// The ID of the rule, see [`LINT_RULES`].
+    pub rule: &'static str,
+    pub glyph: Option<String>,
+    pub layer: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.rule)?;
+        if let Some(glyph) = &self.glyph {
+            write!(f, "glyph '{}', ", glyph)?;
+        }
+        if let Some(layer) = &self.layer {
+            write!(f, "layer '{}', ", layer)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+fn issue(rule: &'static str, glyph: &Glyph, layer: Option<&Layer>, message: String) -> LintIssue {
+    LintIssue {
+        rule,
+        glyph: Some(glyph.glyphname.to_string()),
+        layer: layer.map(|layer| layer.name.clone().unwrap_or_else(|| layer.layer_id.clone())),
+        message,
+    }
+}
+
+/// Find glyph names that are not valid in UFOs in an unparsed font, as
+/// [`Font`] cannot hold them.
+pub fn lint_glyph_names(plist: &Plist) -> Vec<LintIssue> {
+    plist
+        .get("glyphs")
+        .and_then(Plist::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|glyph| {
+            let name = match glyph.get("glyphname")? {
+                Plist::String(name) => name,
+                // Glyphs.app writes "infinity" unquoted, see the Name parser.
+                Plist::Float(f) if f.is_infinite() => return None,
+                other => {
+                    return Some(LintIssue {
+                        rule: "invalid-glyph-name",
+                        glyph: None,
+                        layer: None,
+                        message: format!("glyph name {:?} is not a string", other),
+                    })
+                }
+            };
+            let error = norad::Name::new(name).err()?;
+            Some(LintIssue {
+                rule: "invalid-glyph-name",
+                glyph: Some(name.clone()),
+                layer: None,
+                message: format!("not a valid UFO glyph name: {:?}", error),
+            })
+        })
+        .collect()
+}
+
+impl Font {
+    /// The rule IDs disabled in the font's user data, see
+    /// [`DISABLED_LINT_RULES_KEY`].
+    pub fn disabled_lint_rules(&self) -> HashSet<String> {
+        self.other_stuff
+            .get("userData")
+            .and_then(|user_data| user_data.get(DISABLED_LINT_RULES_KEY))
+            .and_then(Plist::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|rule| rule.as_str().map(String::from))
+            .collect()
+    }
+
+    /// Run all lint rules but the `disabled` ones.
+    pub fn lint(&self, disabled: &HashSet<String>) -> Vec<LintIssue> {
+        let mut issues = Vec::new();
+        self.lint_unicodes(&mut issues);
+        for glyph in &self.glyphs {
+            self.lint_glyph(glyph, &mut issues);
+        }
+        self.lint_glyph_order(&mut issues);
+        issues.retain(|issue| !disabled.contains(issue.rule));
+        issues
+    }
+
+    fn lint_unicodes(&self, issues: &mut Vec<LintIssue>) {
+        let mut glyphs_by_codepoint: BTreeMap<char, Vec<&str>> = BTreeMap::new();
+        for glyph in &self.glyphs {
+            for codepoint in glyph.unicode.iter().flat_map(|unicodes| unicodes.iter()) {
+                glyphs_by_codepoint
+                    .entry(codepoint)
+                    .or_default()
+                    .push(glyph.glyphname.as_str());
+            }
+        }
+        for (codepoint, glyphs) in glyphs_by_codepoint {
+            if glyphs.len() > 1 {
+                issues.push(LintIssue {
+                    rule: "duplicate-unicode",
+                    glyph: None,
+                    layer: None,
+                    message: format!(
+                        "U+{:04X} is assigned to {}",
+                        codepoint as u32,
+                        glyphs.join(", ")
+                    ),
+                });
+            }
+        }
+    }
+
+    fn lint_glyph(&self, glyph: &Glyph, issues: &mut Vec<LintIssue>) {
+        let exports = glyph.other_stuff.get("export").and_then(Plist::as_i64) != Some(0);
+        let grid = self
+            .other_stuff
+            .get("gridLength")
+            .and_then(Plist::as_f64)
+            .unwrap_or(1.0);
+
+        let has_unicodes = glyph
+            .unicode
+            .iter()
+            .flat_map(|unicodes| unicodes.iter())
+            .any(|c| !is_blank(c));
+        let has_outlines = |layer: &Layer| {
+            layer.paths.iter().flatten().next().is_some()
+                || layer.components.iter().flatten().next().is_some()
+        };
+        let is_empty = !self
+            .font_master
+            .iter()
+            .filter_map(|master| glyph.get_layer(&master.id))
+            .any(has_outlines);
+        if has_unicodes && is_empty {
+            issues.push(issue(
+                "empty-glyph-with-unicode",
+                glyph,
+                None,
+                "has codepoints but no outlines on any master".into(),
+            ));
+        }
+
+        for layer in &glyph.layers {
+            for component in layer.components.iter().flatten() {
+                if component.name == glyph.glyphname.as_str() {
+                    issues.push(issue(
+                        "self-reference",
+                        glyph,
+                        Some(layer),
+                        "a component references the glyph itself".into(),
+                    ));
+                } else if self.get_glyph(&component.name).is_none() {
+                    issues.push(issue(
+                        "missing-component",
+                        glyph,
+                        Some(layer),
+                        format!("component base glyph '{}' does not exist", component.name),
+                    ));
+                }
+                if let Err(e) = norad::Name::new(&component.name) {
+                    issues.push(issue(
+                        "invalid-glyph-name",
+                        glyph,
+                        Some(layer),
+                        format!("component name '{}' is not valid: {:?}", component.name, e),
+                    ));
+                }
+            }
+
+            if exports {
+                let open_paths = layer.paths.iter().flatten().filter(|p| !p.closed).count();
+                if open_paths > 0 {
+                    issues.push(issue(
+                        "open-path",
+                        glyph,
+                        Some(layer),
+                        format!("{} open path(s)", open_paths),
+                    ));
+                }
+            }
+
+            if layer.anchors.iter().flatten().any(|a| a.name.is_empty()) {
+                issues.push(issue(
+                    "unnamed-anchor",
+                    glyph,
+                    Some(layer),
+                    "an anchor has no name".into(),
+                ));
+            }
+
+            if grid > 0.0 {
+                let off_grid: Vec<_> = layer
+                    .paths
+                    .iter()
+                    .flatten()
+                    .flat_map(|path| path.nodes.iter().map(|node| node.pt))
+                    .chain(layer.anchors.iter().flatten().map(|anchor| anchor.position))
+                    .filter(|pt| pt.x % grid != 0.0 || pt.y % grid != 0.0)
+                    .collect();
+                if let Some(first) = off_grid.first() {
+                    issues.push(issue(
+                        "off-grid",
+                        glyph,
+                        Some(layer),
+                        format!(
+                            "{} point(s) off the grid of {}, the first at ({}, {})",
+                            off_grid.len(),
+                            grid,
+                            first.x,
+                            first.y
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn lint_glyph_order(&self, issues: &mut Vec<LintIssue>) {
+        let names: HashSet<&str> = self.glyphs.iter().map(|g| g.glyphname.as_str()).collect();
+        let glyph_order = self
+            .custom_parameter("glyphOrder")
+            .and_then(Plist::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Plist::as_str);
+        for name in glyph_order {
+            if !names.contains(name) {
+                issues.push(LintIssue {
+                    rule: "missing-glyph-order-glyph",
+                    glyph: Some(name.to_string()),
+                    layer: None,
+                    message: "listed in the glyphOrder but does not exist".into(),
+                });
+            }
+        }
+    }
+}
+
+/// Whether a codepoint is drawn as nothing, like spaces and joiners.
+fn is_blank(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{034F}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    #[test]
+    fn lint_rules() {
+        let plist = Plist::parse(
+            r#"{
+            familyName = Test;
+            versionMajor = 1;
+            versionMinor = 0;
+            unitsPerEm = 1000;
+            customParameters = ({name = glyphOrder; value = (a, b, space, ghost);});
+            userData = {com.daltonmaag.glyphsExchange.disabledLintRules = (off-grid);};
+            fontMaster = ({id = m01;});
+            glyphs = (
+                {glyphname = a; unicode = 0061; layers = (
+                    {layerId = m01; width = 500;
+                     anchors = ({name = ""; position = "{0, 0}";});
+                     paths = ({closed = 0; nodes = ("0 0 LINE", "10.5 0 LINE");});}
+                );},
+                {glyphname = b; unicode = 0061; layers = (
+                    {layerId = m01; width = 500; components = ({name = b;}, {name = c;});}
+                );},
+                {glyphname = space; unicode = 0020; layers = ({layerId = m01; width = 200;});},
+                {glyphname = e; unicode = 0065; layers = ({layerId = m01; width = 200;});}
+            );
+        }"#,
+        )
+        .unwrap();
+        assert!(lint_glyph_names(&plist).is_empty());
+        let font: Font = FromPlist::from_plist(plist).unwrap();
+
+        let issues = font.lint(&HashSet::new());
+        let rules: Vec<_> = issues.iter().map(|issue| issue.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                "duplicate-unicode",
+                "open-path",
+                "unnamed-anchor",
+                "off-grid",
+                "self-reference",
+                "missing-component",
+                "empty-glyph-with-unicode",
+                "missing-glyph-order-glyph",
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "duplicate-unicode: U+0061 is assigned to a, b"
+        );
+
+        let disabled = font.disabled_lint_rules();
+        assert!(font.lint(&disabled).iter().all(|i| i.rule != "off-grid"));
+    }
+
+    #[test]
+    fn invalid_glyph_names() {
+        let plist =
+            Plist::parse(r#"{glyphs = ({glyphname = "a\012b";}, {glyphname = c;});}"#).unwrap();
+        let issues = lint_glyph_names(&plist);
+        assert_eq!(issues.len(), 1);
+    }
+}
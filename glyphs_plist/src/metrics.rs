@@ -0,0 +1,254 @@
+//! Metrics of masters and layers.
+
+use crate::font::{Font, FontMaster, Layer};
+use crate::plist::Plist;
+
+/// The metrics of a master, as in the UFO fontinfo. The italic angle is
+/// Glyphs.app's, positive for a lean to the right.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MasterMetrics {
+    pub ascender: Option<f64>,
+    pub cap_height: Option<f64>,
+    pub x_height: Option<f64>,
+    pub descender: Option<f64>,
+    pub italic_angle: Option<f64>,
+    /// The metric custom parameters that are set, in the order of
+    /// [`METRIC_PARAMETERS`].
+    pub parameters: Vec<(&'static str, f64)>,
+}
+
+/// The master custom parameters for OS/2, hhea and post table metrics.
+pub const METRIC_PARAMETERS: &[&str] = &[
+    "typoAscender",
+    "typoDescender",
+    "typoLineGap",
+    "hheaAscender",
+    "hheaDescender",
+    "hheaLineGap",
+    "winAscent",
+    "winDescent",
+    "underlinePosition",
+    "underlineThickness",
+    "strikeoutPosition",
+    "strikeoutSize",
+    "subscriptXOffset",
+    "subscriptXSize",
+    "subscriptYOffset",
+    "subscriptYSize",
+    "superscriptXOffset",
+    "superscriptXSize",
+    "superscriptYOffset",
+    "superscriptYSize",
+];
+
+/// The vhea values of a master, as in the UFO fontinfo.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerticalMetrics {
+    pub vert_typo_ascender: Option<i64>,
+    pub vert_typo_descender: Option<i64>,
+    pub vert_typo_line_gap: Option<i64>,
+    pub caret_slope_rise: Option<i64>,
+    pub caret_slope_run: Option<i64>,
+    pub caret_offset: Option<i64>,
+}
+
+impl VerticalMetrics {
+    /// The values by their Glyphs.app custom parameter names.
+    fn parameters(&self) -> [(&'static str, Option<i64>); 6] {
+        [
+            ("vheaVertAscender", self.vert_typo_ascender),
+            ("vheaVertDescender", self.vert_typo_descender),
+            ("vheaVertLineGap", self.vert_typo_line_gap),
+            ("vheaCaretSlopeRise", self.caret_slope_rise),
+            ("vheaCaretSlopeRun", self.caret_slope_run),
+            ("vheaCaretOffset", self.caret_offset),
+        ]
+    }
+
+    fn parameters_mut(&mut self) -> [(&'static str, &mut Option<i64>); 6] {
+        [
+            ("vheaVertAscender", &mut self.vert_typo_ascender),
+            ("vheaVertDescender", &mut self.vert_typo_descender),
+            ("vheaVertLineGap", &mut self.vert_typo_line_gap),
+            ("vheaCaretSlopeRise", &mut self.caret_slope_rise),
+            ("vheaCaretSlopeRun", &mut self.caret_slope_run),
+            ("vheaCaretOffset", &mut self.caret_offset),
+        ]
+    }
+}
+
+impl Font {
+    /// The metrics of `master`. The drawn metrics are the master fields in
+    /// Glyphs 2 and the values of the unfiltered metrics in Glyphs 3, custom
+    /// parameters are looked up on the master first, then on the font.
+    pub fn master_metrics(&self, master: &FontMaster) -> MasterMetrics {
+        let parameters = METRIC_PARAMETERS
+            .iter()
+            .filter_map(|&name| {
+                let value = master
+                    .custom_parameter(name)
+                    .or_else(|| self.custom_parameter(name))?;
+                Some((name, value.as_f64()?))
+            })
+            .collect();
+        let Some(metrics) = &self.metrics else {
+            return MasterMetrics {
+                ascender: master.ascender.map(|v| v as f64),
+                cap_height: master.cap_height.map(|v| v as f64),
+                x_height: master.x_height.map(|v| v as f64),
+                descender: master.descender.map(|v| v as f64),
+                italic_angle: master.italic_angle,
+                parameters,
+            };
+        };
+        let value = |metric_type: &str| {
+            let index = metrics
+                .iter()
+                .position(|m| m.filter.is_none() && m.r#type.as_deref() == Some(metric_type))?;
+            let value = master.metric_values.as_ref()?.get(index)?;
+            // Glyphs.app leaves out zero positions.
+            Some(value.pos.unwrap_or(0.0))
+        };
+        MasterMetrics {
+            ascender: value("ascender"),
+            cap_height: value("cap height"),
+            x_height: value("x-height"),
+            descender: value("descender"),
+            italic_angle: value("italic angle"),
+            parameters,
+        }
+    }
+
+    /// Where layers of `master` without a vertical origin of their own have
+    /// it: the "typoAscender" custom parameter, or the ascender.
+    pub fn default_vertical_origin(&self, master: &FontMaster) -> Option<f64> {
+        let metrics = self.master_metrics(master);
+        metrics
+            .parameters
+            .iter()
+            .find(|(name, _)| *name == "typoAscender")
+            .map(|&(_, value)| value)
+            .or(metrics.ascender)
+    }
+
+    /// The vertical metrics of `master`, from its custom parameters or
+    /// those of the font.
+    pub fn vertical_metrics(&self, master: &FontMaster) -> VerticalMetrics {
+        let mut metrics = VerticalMetrics::default();
+        for (name, value) in metrics.parameters_mut() {
+            *value = master
+                .custom_parameter(name)
+                .or_else(|| self.custom_parameter(name))
+                .and_then(Plist::as_i64);
+        }
+        metrics
+    }
+}
+
+impl FontMaster {
+    /// Store `metrics` in the master the Glyphs 2 way: the drawn metrics,
+    /// rounded to integers, in the master fields, everything else as custom
+    /// parameters.
+    pub fn set_master_metrics(&mut self, metrics: &MasterMetrics) {
+        let round = |value: Option<f64>| value.map(|v| v.round() as i64);
+        self.ascender = round(metrics.ascender);
+        self.cap_height = round(metrics.cap_height);
+        self.x_height = round(metrics.x_height);
+        self.descender = round(metrics.descender);
+        self.italic_angle = metrics.italic_angle;
+        self.metric_values = None;
+        for &(name, value) in &metrics.parameters {
+            self.set_custom_parameter(name, value.into());
+        }
+    }
+
+    /// Store `metrics` as custom parameters of the master.
+    pub fn set_vertical_metrics(&mut self, metrics: &VerticalMetrics) {
+        for (name, value) in metrics.parameters() {
+            if let Some(value) = value {
+                self.set_custom_parameter(name, value.into());
+            }
+        }
+    }
+}
+
+impl Layer {
+    /// The explicit vertical origin of the layer as a y coordinate. Glyphs.app
+    /// stores it as an offset down from the default vertical origin, see
+    /// [`Font::default_vertical_origin`].
+    pub fn vertical_origin(&self, default_origin: f64) -> Option<f64> {
+        Some(default_origin - self.vert_origin?)
+    }
+
+    /// Set the explicit vertical origin of the layer, see
+    /// [`Layer::vertical_origin`].
+    pub fn set_vertical_origin(&mut self, origin: Option<f64>, default_origin: f64) {
+        self.vert_origin = origin.map(|origin| default_origin - origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    #[test]
+    fn glyphs3_master_metrics() {
+        let font = Font::load(&"../testdata/NewFontG3.glyphs").unwrap();
+        let master = &font.font_master[0];
+        let metrics = font.master_metrics(master);
+        assert_eq!(metrics.ascender, Some(800.0));
+        assert_eq!(metrics.descender, Some(-200.0));
+        assert_eq!(metrics.x_height, None);
+        assert_eq!(
+            font.alignment_zones(master)
+                .iter()
+                .map(|zone| (zone.position, zone.size))
+                .collect::<Vec<_>>(),
+            vec![(-200.0, -16.0), (0.0, -16.0), (800.0, 16.0)]
+        );
+    }
+
+    #[test]
+    fn vertical_metrics_and_origin() {
+        let font: Font = FromPlist::from_plist(
+            Plist::parse(
+                r#"{
+                familyName = Test;
+                versionMajor = 1;
+                versionMinor = 0;
+                unitsPerEm = 1000;
+                glyphs = ();
+                customParameters = ({name = vheaVertLineGap; value = 0;});
+                fontMaster = ({
+                    id = m01;
+                    ascender = 800;
+                    customParameters = (
+                        {name = typoAscender; value = 880;},
+                        {name = vheaVertAscender; value = 500;},
+                        {name = vheaVertDescender; value = -500;}
+                    );
+                });
+            }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let master = &font.font_master[0];
+        let metrics = font.vertical_metrics(master);
+        assert_eq!(metrics.vert_typo_ascender, Some(500));
+        assert_eq!(metrics.vert_typo_descender, Some(-500));
+        assert_eq!(metrics.vert_typo_line_gap, Some(0));
+        assert_eq!(metrics.caret_offset, None);
+
+        let default_origin = font.default_vertical_origin(master).unwrap();
+        assert_eq!(default_origin, 880.0);
+        let mut layer = Layer {
+            vert_origin: Some(80.0),
+            ..Default::default()
+        };
+        assert_eq!(layer.vertical_origin(default_origin), Some(800.0));
+        layer.set_vertical_origin(Some(900.0), default_origin);
+        assert_eq!(layer.vert_origin, Some(-20.0));
+    }
+}
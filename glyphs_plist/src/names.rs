@@ -0,0 +1,451 @@
+//! Localized names: the properties of Glyphs 3 fonts and instances, and the
+//! name IDs and language codes they correspond to.
+
+use log::warn;
+
+use crate::font::{Font, Instance, LocalizedValue, Property};
+use crate::plist::Plist;
+
+/// The language of property values that are not localized.
+pub const DEFAULT_LANGUAGE: &str = "dflt";
+
+/// A language, by its OpenType tag as Glyphs.app uses it, its BCP 47 code as
+/// Designspace files use it, and its name table language IDs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    pub tag: &'static str,
+    pub bcp47: &'static str,
+    pub windows_id: u16,
+    pub mac_id: u16,
+}
+
+const fn language(
+    tag: &'static str,
+    bcp47: &'static str,
+    windows_id: u16,
+    mac_id: u16,
+) -> Language {
+    Language {
+        tag,
+        bcp47,
+        windows_id,
+        mac_id,
+    }
+}
+
+/// The languages Glyphs.app offers for localized names.
+pub const LANGUAGES: &[Language] = &[
+    language("ENG", "en", 0x0409, 0),
+    language("FRA", "fr", 0x040C, 1),
+    language("DEU", "de", 0x0407, 2),
+    language("ITA", "it", 0x0410, 3),
+    language("NLD", "nl", 0x0413, 4),
+    language("SVE", "sv", 0x041D, 5),
+    language("ESP", "es", 0x0C0A, 6),
+    language("DAN", "da", 0x0406, 7),
+    language("PTG", "pt", 0x0816, 8),
+    language("NOR", "nb", 0x0414, 9),
+    language("IWR", "he", 0x040D, 10),
+    language("JAN", "ja", 0x0411, 11),
+    language("ARA", "ar", 0x0401, 12),
+    language("FIN", "fi", 0x040B, 13),
+    language("ELL", "el", 0x0408, 14),
+    language("ISL", "is", 0x040F, 15),
+    language("MTS", "mt", 0x043A, 16),
+    language("TRK", "tr", 0x041F, 17),
+    language("HRV", "hr", 0x041A, 18),
+    language("ZHT", "zh-Hant", 0x0404, 19),
+    language("URD", "ur", 0x0420, 20),
+    language("HIN", "hi", 0x0439, 21),
+    language("THA", "th", 0x041E, 22),
+    language("KOR", "ko", 0x0412, 23),
+    language("LTH", "lt", 0x0427, 24),
+    language("PLK", "pl", 0x0415, 25),
+    language("HUN", "hu", 0x040E, 26),
+    language("ETI", "et", 0x0425, 27),
+    language("LVI", "lv", 0x0426, 28),
+    language("FAR", "fa", 0x0429, 31),
+    language("RUS", "ru", 0x0419, 32),
+    language("ZHS", "zh-Hans", 0x0804, 33),
+    language("ROM", "ro", 0x0418, 37),
+    language("CSY", "cs", 0x0405, 38),
+    language("SKY", "sk", 0x041B, 39),
+    language("SLV", "sl", 0x0424, 40),
+    language("SRB", "sr", 0x0C1A, 42),
+    language("BGR", "bg", 0x0402, 44),
+    language("UKR", "uk", 0x0422, 45),
+    language("VIT", "vi", 0x042A, 80),
+    language("IND", "id", 0x0421, 81),
+    language("CAT", "ca", 0x0403, 130),
+];
+
+impl Language {
+    pub fn from_tag(tag: &str) -> Option<&'static Language> {
+        LANGUAGES.iter().find(|l| l.tag == tag)
+    }
+
+    /// Look up a BCP 47 code, ignoring case.
+    pub fn from_bcp47(code: &str) -> Option<&'static Language> {
+        LANGUAGES
+            .iter()
+            .find(|l| l.bcp47.eq_ignore_ascii_case(code))
+    }
+
+    pub fn from_windows_id(id: u16) -> Option<&'static Language> {
+        LANGUAGES.iter().find(|l| l.windows_id == id)
+    }
+
+    pub fn from_mac_id(id: u16) -> Option<&'static Language> {
+        LANGUAGES.iter().find(|l| l.mac_id == id)
+    }
+}
+
+/// The name table platform and encoding of localized names.
+pub(crate) const WINDOWS_PLATFORM_ID: u16 = 3;
+pub(crate) const WINDOWS_UNICODE_BMP_ID: u16 = 1;
+
+/// The top-level keys Glyphs 2 fonts keep some default names under, by
+/// property key.
+const GLYPHS2_FONT_KEYS: &[(&str, &str)] = &[
+    ("copyrights", "copyright"),
+    ("designers", "designer"),
+    ("designerURL", "designerURL"),
+    ("manufacturers", "manufacturer"),
+    ("manufacturerURL", "manufacturerURL"),
+];
+
+/// The custom parameters Glyphs 2 fonts keep other default names in, by
+/// property key.
+const GLYPHS2_PARAMETERS: &[(&str, &str)] = &[
+    ("compatibleFullNames", "compatibleFullName"),
+    ("descriptions", "description"),
+    ("licenses", "license"),
+    ("licenseURL", "licenseURL"),
+    ("preferredFamilyNames", "preferredFamilyName"),
+    ("preferredSubfamilyNames", "preferredSubfamilyName"),
+    ("sampleTexts", "sampleText"),
+    ("trademarks", "trademark"),
+    ("uniqueID", "uniqueID"),
+    ("versionString", "versionString"),
+    (
+        "variationsPostScriptNamePrefix",
+        "variationsPostScriptNamePrefix",
+    ),
+    ("WWSFamilyName", "WWSFamilyName"),
+    ("WWSSubfamilyName", "WWSSubfamilyName"),
+];
+
+/// The custom parameter for any other name, as "nameID; string" for default
+/// values and "nameID platformID encodingID languageID; string" for
+/// localized ones.
+const NAME_TABLE_ENTRY: &str = "Name Table Entry";
+
+/// The property keys for name table entries, their name IDs, and whether
+/// Glyphs.app allows localized values for them.
+pub(crate) const NAME_PROPERTIES: &[(&str, u16, bool)] = &[
+    ("copyrights", 0, true),
+    ("familyNames", 1, true),
+    ("styleNames", 2, true),
+    ("uniqueID", 3, false),
+    ("compatibleFullNames", 4, true),
+    ("versionString", 5, false),
+    ("postscriptFontName", 6, false),
+    ("trademarks", 7, true),
+    ("manufacturers", 8, true),
+    ("designers", 9, true),
+    ("descriptions", 10, true),
+    ("manufacturerURL", 11, false),
+    ("designerURL", 12, false),
+    ("licenses", 13, true),
+    ("licenseURL", 14, false),
+    ("preferredFamilyNames", 16, true),
+    ("preferredSubfamilyNames", 17, true),
+    ("sampleTexts", 19, true),
+    ("WWSFamilyName", 21, false),
+    ("WWSSubfamilyName", 22, false),
+    ("variationsPostScriptNamePrefix", 25, false),
+];
+
+/// The name ID a property key fills.
+pub fn name_id(key: &str) -> Option<u16> {
+    NAME_PROPERTIES
+        .iter()
+        .find(|(k, ..)| *k == key)
+        .map(|&(_, id, _)| id)
+}
+
+/// The property key for a name ID, and whether it can be localized.
+pub fn property_key(name_id: u16) -> Option<(&'static str, bool)> {
+    NAME_PROPERTIES
+        .iter()
+        .find(|(_, id, _)| *id == name_id)
+        .map(|&(key, _, localizable)| (key, localizable))
+}
+
+impl Property {
+    /// The value for `language`, [`DEFAULT_LANGUAGE`] included.
+    pub fn get(&self, language: &str) -> Option<&str> {
+        if language == DEFAULT_LANGUAGE {
+            if let Some(value) = &self.value {
+                return Some(value);
+            }
+        }
+        self.values
+            .iter()
+            .flatten()
+            .find(|v| v.language == language)
+            .map(|v| v.value.as_str())
+    }
+
+    pub fn default_value(&self) -> Option<&str> {
+        self.get(DEFAULT_LANGUAGE)
+    }
+
+    /// The values for all languages but [`DEFAULT_LANGUAGE`].
+    pub fn localized_values(&self) -> impl Iterator<Item = &LocalizedValue> {
+        self.values
+            .iter()
+            .flatten()
+            .filter(|v| v.language != DEFAULT_LANGUAGE)
+    }
+}
+
+fn property<'a>(properties: &'a Option<Vec<Property>>, key: &str) -> Option<&'a Property> {
+    properties.iter().flatten().find(|p| p.key == key)
+}
+
+impl Font {
+    pub fn property(&self, key: &str) -> Option<&Property> {
+        property(&self.properties, key)
+    }
+
+    /// The names of the font: its properties, or for Glyphs 2 fonts, which
+    /// have none, the top-level keys and custom parameters Glyphs 2 keeps
+    /// them in. `None` if a Glyphs 2 font has no names.
+    pub fn name_properties(&self) -> Option<Vec<Property>> {
+        if let Some(properties) = &self.properties {
+            return Some(properties.clone());
+        }
+        let entries: Vec<_> = self
+            .custom_parameters(NAME_TABLE_ENTRY)
+            .filter_map(Plist::as_str)
+            .filter_map(parse_name_table_entry)
+            .collect();
+        let mut properties = Vec::new();
+        for &(key, name_id, localizable) in NAME_PROPERTIES {
+            // The default family name is a font field of its own.
+            let default = if key == "familyNames" {
+                None
+            } else if let Some(g2_key) = glyphs2_name(GLYPHS2_FONT_KEYS, key) {
+                self.other_stuff.get(g2_key).and_then(Plist::as_str)
+            } else if let Some(name) = glyphs2_name(GLYPHS2_PARAMETERS, key) {
+                self.custom_parameter(name).and_then(Plist::as_str)
+            } else {
+                None
+            };
+            let default = default.or_else(|| {
+                entries
+                    .iter()
+                    .find(|(id, language, _)| *id == name_id && language.is_none())
+                    .map(|&(.., value)| value)
+            });
+            let mut values: Vec<LocalizedValue> = default
+                .map(|value| LocalizedValue {
+                    language: DEFAULT_LANGUAGE.into(),
+                    value: value.into(),
+                })
+                .into_iter()
+                .collect();
+            if localizable {
+                values.extend(entries.iter().filter_map(|&(id, language, value)| {
+                    (id == name_id).then_some(LocalizedValue {
+                        language: language?.tag.into(),
+                        value: value.into(),
+                    })
+                }));
+            }
+            if values.is_empty() {
+                continue;
+            }
+            properties.push(if localizable {
+                Property {
+                    key: key.into(),
+                    values: Some(values),
+                    ..Default::default()
+                }
+            } else {
+                Property {
+                    key: key.into(),
+                    value: default.map(str::to_string),
+                    ..Default::default()
+                }
+            });
+        }
+        (!properties.is_empty()).then_some(properties)
+    }
+
+    /// Store `properties` the Glyphs 2 way, see [`Font::name_properties`].
+    pub fn set_glyphs2_names(&mut self, properties: &[Property]) {
+        for property in properties {
+            let Some(name_id) = name_id(&property.key) else {
+                continue;
+            };
+            let key = property.key.as_str();
+            if let Some(value) = property.default_value() {
+                if key == "familyNames" {
+                    self.family_name = value.into();
+                } else if let Some(g2_key) = glyphs2_name(GLYPHS2_FONT_KEYS, key) {
+                    self.other_stuff
+                        .insert(g2_key.to_string(), value.to_string().into());
+                } else if let Some(name) = glyphs2_name(GLYPHS2_PARAMETERS, key) {
+                    self.set_custom_parameter(name, value.to_string().into());
+                } else {
+                    let entry = format!("{}; {}", name_id, value);
+                    self.add_custom_parameter(NAME_TABLE_ENTRY, entry.into());
+                }
+            }
+            for localized in property.localized_values() {
+                let Some(language) = Language::from_tag(&localized.language) else {
+                    warn!(
+                        "Dropping {} value in unknown language {}",
+                        property.key, localized.language
+                    );
+                    continue;
+                };
+                let entry = format!(
+                    "{} {} {} 0x{:04X}; {}",
+                    name_id,
+                    WINDOWS_PLATFORM_ID,
+                    WINDOWS_UNICODE_BMP_ID,
+                    language.windows_id,
+                    localized.value
+                );
+                self.add_custom_parameter(NAME_TABLE_ENTRY, entry.into());
+            }
+        }
+    }
+}
+
+fn glyphs2_name(table: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|&(_, name)| name)
+}
+
+/// Parse a number of a "Name Table Entry", decimal or hexadecimal.
+fn parse_entry_id(id: &str) -> Option<u16> {
+    match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+/// Parse a "Name Table Entry" into its name ID, language (`None` for the
+/// default value) and string. Entries for other platforms are skipped.
+fn parse_name_table_entry(entry: &str) -> Option<(u16, Option<&'static Language>, &str)> {
+    let (ids, value) = entry.split_once(';')?;
+    let ids = ids
+        .split_whitespace()
+        .map(parse_entry_id)
+        .collect::<Option<Vec<u16>>>()?;
+    let value = value.trim_start();
+    match *ids.as_slice() {
+        [name_id] => Some((name_id, None, value)),
+        [name_id, WINDOWS_PLATFORM_ID, WINDOWS_UNICODE_BMP_ID, language_id] => Some((
+            name_id,
+            Some(Language::from_windows_id(language_id)?),
+            value,
+        )),
+        _ => None,
+    }
+}
+
+impl Instance {
+    pub fn property(&self, key: &str) -> Option<&Property> {
+        property(&self.properties, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    #[test]
+    fn parse_properties() {
+        let plist = Plist::parse(
+            r#"(
+                {key = familyNames; values = ({language = dflt; value = Test;}, {language = DEU; value = Probe;});},
+                {key = designerURL; value = "https://example.com";}
+            )"#,
+        )
+        .unwrap();
+        let properties: Vec<Property> = FromPlist::from_plist(plist).unwrap();
+        assert_eq!(properties[0].default_value(), Some("Test"));
+        assert_eq!(properties[0].get("DEU"), Some("Probe"));
+        let localized: Vec<_> = properties[0].localized_values().collect();
+        assert_eq!(localized.len(), 1);
+        assert_eq!(properties[1].default_value(), Some("https://example.com"));
+
+        assert_eq!(name_id("designers"), Some(9));
+        assert_eq!(property_key(12), Some(("designerURL", false)));
+        let german = Language::from_tag("DEU").unwrap();
+        assert_eq!(Language::from_windows_id(german.windows_id), Some(german));
+        assert_eq!(Language::from_mac_id(2), Some(german));
+        assert_eq!(Language::from_bcp47("zh-hans").unwrap().tag, "ZHS");
+    }
+    #[test]
+    fn glyphs2_names_roundtrip() {
+        let plist = Plist::parse(
+            r#"(
+                {key = designers; values = ({language = dflt; value = Designer;}, {language = DEU; value = Gestalter;});},
+                {key = licenseURL; value = "https://example.com/license";},
+                {key = sampleTexts; values = ({language = dflt; value = Sample;});},
+                {key = styleNames; values = ({language = dflt; value = Regular;}, {language = FRA; value = Normal;});}
+            )"#,
+        )
+        .unwrap();
+        let properties: Vec<Property> = FromPlist::from_plist(plist).unwrap();
+        let mut font = Font::default();
+        assert!(font.name_properties().is_none());
+
+        font.set_glyphs2_names(&properties);
+        assert_eq!(
+            font.other_stuff["designer"],
+            Plist::String("Designer".into())
+        );
+        assert_eq!(
+            font.custom_parameter("licenseURL"),
+            Some(&Plist::String("https://example.com/license".into()))
+        );
+        assert_eq!(
+            font.custom_parameter("sampleText"),
+            Some(&Plist::String("Sample".into()))
+        );
+        let entries: Vec<_> = font
+            .custom_parameters("Name Table Entry")
+            .filter_map(Plist::as_str)
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                "9 3 1 0x0407; Gestalter",
+                "2; Regular",
+                "2 3 1 0x040C; Normal"
+            ]
+        );
+
+        let localized = |property: &Property| -> Vec<(String, String)> {
+            property
+                .localized_values()
+                .map(|v| (v.language.clone(), v.value.clone()))
+                .collect()
+        };
+        let read = font.name_properties().unwrap();
+        assert_eq!(read.len(), properties.len());
+        for property in &properties {
+            let read = read.iter().find(|p| p.key == property.key).unwrap();
+            assert_eq!(read.default_value(), property.default_value());
+            assert_eq!(localized(read), localized(property));
+        }
+    }
+}
@@ -1,4 +1,23 @@
-use crate::{Anchor, Component, Node, NodeType, Path};
+use std::collections::HashMap;
+
+use log::warn;
+
+use crate::names::{NAME_PROPERTIES, WINDOWS_PLATFORM_ID, WINDOWS_UNICODE_BMP_ID};
+use crate::{
+    name_id, property_key, Anchor, Component, Font, Language, LocalizedValue, MasterMetrics, Node,
+    NodeType, Path, Plist, PostscriptHinting, Property, VerticalMetrics, DEFAULT_LANGUAGE,
+    METRIC_PARAMETERS,
+};
+
+/// The key UFO identifiers are kept under in the user data of anchors and
+/// nodes and the attributes of paths.
+const IDENTIFIER_KEY: &str = "com.daltonmaag.glyphs-exchange.identifier";
+/// The node user data key Glyphs.app keeps point names under.
+const NODE_NAME_KEY: &str = "name";
+
+fn identifier_from(value: &Plist) -> Option<norad::Identifier> {
+    norad::Identifier::new(value.as_str()?).ok()
+}
 
 impl From<&norad::Contour> for Path {
     fn from(contour: &norad::Contour) -> Self {
@@ -7,14 +26,22 @@ impl From<&norad::Contour> for Path {
             .iter()
             .map(|contour| contour.into())
             .collect();
-        if contour.is_closed() {
+        if contour.is_closed() && !nodes.is_empty() {
             // In Glyphs.app, the starting node of a closed contour is
             // always stored at the end of the nodes list.
             nodes.rotate_left(1);
         }
+        let attr = contour.identifier().map(|identifier| {
+            HashMap::from([(
+                IDENTIFIER_KEY.to_string(),
+                identifier.as_str().to_string().into(),
+            )])
+        });
         Self {
             closed: contour.is_closed(),
             nodes,
+            attr,
+            other_stuff: HashMap::new(),
         }
     }
 }
@@ -24,19 +51,61 @@ impl From<&Path> for norad::Contour {
         let mut points: Vec<norad::ContourPoint> =
             path.nodes.iter().map(|node| node.into()).collect();
         if !path.closed {
-            assert!(points[0].typ == norad::PointType::Line && !points[0].smooth);
-            points[0].typ = norad::PointType::Move;
-        } else {
+            // Open UFO contours start with a move and end on-curve, so drop
+            // off-curve points dangling off either end.
+            let is_off_curve = |p: &&norad::ContourPoint| p.typ == norad::PointType::OffCurve;
+            let leading = points.iter().take_while(is_off_curve).count();
+            if leading == points.len() {
+                if !points.is_empty() {
+                    warn!("Dropping open path with only off-curve points");
+                }
+                points.clear();
+            } else {
+                let trailing = points.iter().rev().take_while(is_off_curve).count();
+                if leading + trailing > 0 {
+                    warn!(
+                        "Dropping {} off-curve points at the ends of an open path",
+                        leading + trailing
+                    );
+                }
+                points.truncate(points.len() - trailing);
+                points.drain(..leading);
+                points[0].typ = norad::PointType::Move;
+                points[0].smooth = false;
+            }
+        } else if !points.is_empty() {
             // In Glyphs.app, the starting node of a closed contour is
             // always stored at the end of the nodes list.
             points.rotate_right(1);
         }
-        Self::new(points, None, None)
+        let identifier = path
+            .attr
+            .as_ref()
+            .and_then(|attr| attr.get(IDENTIFIER_KEY))
+            .and_then(identifier_from);
+        Self::new(points, identifier, None)
     }
 }
 
 impl From<&norad::ContourPoint> for Node {
+    /// The point name, identifier and lib go into the node user data. Lib
+    /// values that Glyphs.app plists cannot hold are dropped.
     fn from(point: &norad::ContourPoint) -> Self {
+        let mut user_data: HashMap<String, Plist> = point
+            .lib()
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.clone(), value.try_into().ok()?)))
+            .collect();
+        if let Some(name) = &point.name {
+            user_data.insert(NODE_NAME_KEY.into(), name.to_string().into());
+        }
+        if let Some(identifier) = point.identifier() {
+            user_data.insert(
+                IDENTIFIER_KEY.into(),
+                identifier.as_str().to_string().into(),
+            );
+        }
         Self {
             pt: kurbo::Point::new(point.x, point.y),
             node_type: match (&point.typ, point.smooth) {
@@ -49,6 +118,8 @@ impl From<&norad::ContourPoint> for Node {
                 (norad::PointType::QCurve, true) => NodeType::QCurveSmooth,
                 (norad::PointType::QCurve, false) => NodeType::QCurve,
             },
+            user_data: (!user_data.is_empty()).then_some(user_data),
+            glyphs3: false,
         }
     }
 }
@@ -64,7 +135,27 @@ impl From<&Node> for norad::ContourPoint {
             NodeType::QCurve => (norad::PointType::QCurve, false),
             NodeType::QCurveSmooth => (norad::PointType::QCurve, true),
         };
-        Self::new(node.pt.x, node.pt.y, typ, smooth, None, None, None)
+        let mut name = None;
+        let mut identifier = None;
+        let mut lib = norad::Plist::new();
+        for (key, value) in node.user_data.iter().flatten() {
+            match key.as_str() {
+                NODE_NAME_KEY => name = value.as_str().and_then(|n| norad::Name::new(n).ok()),
+                IDENTIFIER_KEY => identifier = identifier_from(value),
+                _ => {
+                    lib.insert(key.clone(), value.clone().into());
+                }
+            }
+        }
+        Self::new(
+            node.pt.x,
+            node.pt.y,
+            typ,
+            smooth,
+            name,
+            identifier,
+            (!lib.is_empty()).then_some(lib),
+        )
     }
 }
 
@@ -77,7 +168,7 @@ impl From<&norad::Component> for Component {
             } else {
                 Some(component.transform.into())
             },
-            other_stuff: Default::default(),
+            ..Default::default()
         }
     }
 }
@@ -96,12 +187,48 @@ impl TryFrom<&Component> for norad::Component {
     }
 }
 
-impl From<&norad::Anchor> for Anchor {
-    fn from(anchor: &norad::Anchor) -> Self {
-        Self {
-            name: anchor.name.as_ref().unwrap().as_str().to_string(),
-            position: kurbo::Point::new(anchor.x, anchor.y),
+/// The anchor user data key for UFO anchor colours.
+const ANCHOR_COLOR_KEY: &str = "com.daltonmaag.glyphs-exchange.color";
+/// The UFO anchor lib key for the Glyphs 3 anchor orientation.
+const ANCHOR_ORIENTATION_KEY: &str = "com.schriftgestaltung.orientation";
+
+impl TryFrom<&norad::Anchor> for Anchor {
+    type Error = String;
+
+    /// The anchor lib becomes the user data, which also keeps the identifier
+    /// and colour. Glyphs.app requires anchors to have a name.
+    fn try_from(anchor: &norad::Anchor) -> Result<Self, Self::Error> {
+        let name = anchor
+            .name
+            .as_ref()
+            .ok_or_else(|| format!("anchor at ({}, {}) has no name", anchor.x, anchor.y))?;
+        let mut user_data: HashMap<String, Plist> = HashMap::new();
+        let mut orientation = None;
+        for (key, value) in anchor.lib().into_iter().flatten() {
+            if key == ANCHOR_ORIENTATION_KEY {
+                orientation = value.as_string().map(str::to_string);
+            } else {
+                user_data.insert(key.clone(), value.try_into()?);
+            }
+        }
+        if let Some(identifier) = anchor.identifier() {
+            user_data.insert(
+                IDENTIFIER_KEY.into(),
+                identifier.as_str().to_string().into(),
+            );
+        }
+        if let Some(color) = &anchor.color {
+            user_data.insert(ANCHOR_COLOR_KEY.into(), color.to_rgba_string().into());
         }
+
+        Ok(Self {
+            name: name.as_str().to_string(),
+            position: kurbo::Point::new(anchor.x, anchor.y),
+            orientation,
+            user_data: (!user_data.is_empty()).then_some(user_data),
+            glyphs3: false,
+            other_stuff: HashMap::new(),
+        })
     }
 }
 
@@ -110,13 +237,559 @@ impl TryFrom<&Anchor> for norad::Anchor {
 
     fn try_from(anchor: &Anchor) -> Result<Self, Self::Error> {
         let name = norad::Name::new(&anchor.name)?;
+        let mut identifier = None;
+        let mut color = None;
+        let mut lib = norad::Plist::new();
+        for (key, value) in anchor.user_data.iter().flatten() {
+            match key.as_str() {
+                IDENTIFIER_KEY => identifier = identifier_from(value),
+                ANCHOR_COLOR_KEY => color = value.as_str().and_then(|c| c.parse().ok()),
+                _ => {
+                    lib.insert(key.clone(), value.clone().into());
+                }
+            }
+        }
+        if let Some(orientation) = &anchor.orientation {
+            lib.insert(ANCHOR_ORIENTATION_KEY.into(), orientation.clone().into());
+        }
+
         Ok(Self::new(
             anchor.position.x,
             anchor.position.y,
             Some(name),
-            None,
-            None,
-            None,
+            color,
+            identifier,
+            (!lib.is_empty()).then_some(lib),
         ))
     }
 }
+
+impl From<&norad::FontInfo> for PostscriptHinting {
+    fn from(info: &norad::FontInfo) -> Self {
+        Self {
+            blue_values: info.postscript_blue_values.clone().unwrap_or_default(),
+            other_blues: info.postscript_other_blues.clone().unwrap_or_default(),
+            family_blues: info.postscript_family_blues.clone().unwrap_or_default(),
+            family_other_blues: info
+                .postscript_family_other_blues
+                .clone()
+                .unwrap_or_default(),
+            stem_snap_h: info.postscript_stem_snap_h.clone().unwrap_or_default(),
+            stem_snap_v: info.postscript_stem_snap_v.clone().unwrap_or_default(),
+            blue_scale: info.postscript_blue_scale,
+            blue_shift: info.postscript_blue_shift,
+            blue_fuzz: info.postscript_blue_fuzz,
+        }
+    }
+}
+
+impl PostscriptHinting {
+    /// Write the hinting values into `info`, removing the empty ones.
+    pub fn apply_to_font_info(&self, info: &mut norad::FontInfo) {
+        let non_empty = |values: &Vec<f64>| (!values.is_empty()).then(|| values.clone());
+        info.postscript_blue_values = non_empty(&self.blue_values);
+        info.postscript_other_blues = non_empty(&self.other_blues);
+        info.postscript_family_blues = non_empty(&self.family_blues);
+        info.postscript_family_other_blues = non_empty(&self.family_other_blues);
+        info.postscript_stem_snap_h = non_empty(&self.stem_snap_h);
+        info.postscript_stem_snap_v = non_empty(&self.stem_snap_v);
+        info.postscript_blue_scale = self.blue_scale;
+        info.postscript_blue_shift = self.blue_shift;
+        info.postscript_blue_fuzz = self.blue_fuzz;
+    }
+}
+
+/// A fontinfo field for a metric custom parameter.
+enum MetricField<'a> {
+    Integer(&'a mut Option<norad::fontinfo::Integer>),
+    NonNegativeInteger(&'a mut Option<norad::fontinfo::NonNegativeInteger>),
+    Float(&'a mut Option<f64>),
+}
+
+impl MetricField<'_> {
+    fn get(&self) -> Option<f64> {
+        match self {
+            MetricField::Integer(field) => field.map(f64::from),
+            MetricField::NonNegativeInteger(field) => field.map(f64::from),
+            MetricField::Float(field) => **field,
+        }
+    }
+
+    fn set(self, value: Option<f64>) {
+        match self {
+            MetricField::Integer(field) => {
+                *field = value.map(|v| v.round() as norad::fontinfo::Integer)
+            }
+            MetricField::NonNegativeInteger(field) => {
+                *field = value.map(|v| v.round().max(0.0) as norad::fontinfo::NonNegativeInteger)
+            }
+            MetricField::Float(field) => *field = value,
+        }
+    }
+}
+
+/// The fontinfo field for the metric custom parameter `parameter`.
+fn metric_field<'a>(info: &'a mut norad::FontInfo, parameter: &str) -> Option<MetricField<'a>> {
+    use MetricField::*;
+
+    Some(match parameter {
+        "typoAscender" => Integer(&mut info.open_type_os2_typo_ascender),
+        "typoDescender" => Integer(&mut info.open_type_os2_typo_descender),
+        "typoLineGap" => Integer(&mut info.open_type_os2_typo_line_gap),
+        "hheaAscender" => Integer(&mut info.open_type_hhea_ascender),
+        "hheaDescender" => Integer(&mut info.open_type_hhea_descender),
+        "hheaLineGap" => Integer(&mut info.open_type_hhea_line_gap),
+        "winAscent" => NonNegativeInteger(&mut info.open_type_os2_win_ascent),
+        "winDescent" => NonNegativeInteger(&mut info.open_type_os2_win_descent),
+        "underlinePosition" => Float(&mut info.postscript_underline_position),
+        "underlineThickness" => Float(&mut info.postscript_underline_thickness),
+        "strikeoutPosition" => Integer(&mut info.open_type_os2_strikeout_position),
+        "strikeoutSize" => Integer(&mut info.open_type_os2_strikeout_size),
+        "subscriptXOffset" => Integer(&mut info.open_type_os2_subscript_x_offset),
+        "subscriptXSize" => Integer(&mut info.open_type_os2_subscript_x_size),
+        "subscriptYOffset" => Integer(&mut info.open_type_os2_subscript_y_offset),
+        "subscriptYSize" => Integer(&mut info.open_type_os2_subscript_y_size),
+        "superscriptXOffset" => Integer(&mut info.open_type_os2_superscript_x_offset),
+        "superscriptXSize" => Integer(&mut info.open_type_os2_superscript_x_size),
+        "superscriptYOffset" => Integer(&mut info.open_type_os2_superscript_y_offset),
+        "superscriptYSize" => Integer(&mut info.open_type_os2_superscript_y_size),
+        _ => return None,
+    })
+}
+
+impl From<&norad::FontInfo> for MasterMetrics {
+    fn from(info: &norad::FontInfo) -> Self {
+        // Reading through a copy saves a read-only twin of `metric_field`.
+        let mut fields = info.clone();
+        Self {
+            ascender: info.ascender,
+            cap_height: info.cap_height,
+            x_height: info.x_height,
+            descender: info.descender,
+            italic_angle: info.italic_angle.map(|angle| -angle),
+            parameters: METRIC_PARAMETERS
+                .iter()
+                .filter_map(|&name| Some((name, metric_field(&mut fields, name)?.get()?)))
+                .collect(),
+        }
+    }
+}
+
+impl MasterMetrics {
+    /// Write the metrics into `info`, removing the unset ones.
+    pub fn apply_to_font_info(&self, info: &mut norad::FontInfo) {
+        info.ascender = self.ascender;
+        info.cap_height = self.cap_height;
+        info.x_height = self.x_height;
+        info.descender = self.descender;
+        info.italic_angle = self.italic_angle.map(|angle| -angle);
+        for &name in METRIC_PARAMETERS {
+            let value = self
+                .parameters
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|&(_, value)| value);
+            if let Some(field) = metric_field(info, name) {
+                field.set(value);
+            }
+        }
+    }
+}
+
+impl From<&norad::FontInfo> for VerticalMetrics {
+    fn from(info: &norad::FontInfo) -> Self {
+        let value = |value: Option<norad::fontinfo::Integer>| value.map(i64::from);
+        Self {
+            vert_typo_ascender: value(info.open_type_vhea_vert_typo_ascender),
+            vert_typo_descender: value(info.open_type_vhea_vert_typo_descender),
+            vert_typo_line_gap: value(info.open_type_vhea_vert_typo_line_gap),
+            caret_slope_rise: value(info.open_type_vhea_caret_slope_rise),
+            caret_slope_run: value(info.open_type_vhea_caret_slope_run),
+            caret_offset: value(info.open_type_vhea_caret_offset),
+        }
+    }
+}
+
+impl VerticalMetrics {
+    /// Write the vhea values into `info`.
+    pub fn apply_to_font_info(&self, info: &mut norad::FontInfo) {
+        let value = |value: Option<i64>| value.map(|v| v as norad::fontinfo::Integer);
+        info.open_type_vhea_vert_typo_ascender = value(self.vert_typo_ascender);
+        info.open_type_vhea_vert_typo_descender = value(self.vert_typo_descender);
+        info.open_type_vhea_vert_typo_line_gap = value(self.vert_typo_line_gap);
+        info.open_type_vhea_caret_slope_rise = value(self.caret_slope_rise);
+        info.open_type_vhea_caret_slope_run = value(self.caret_slope_run);
+        info.open_type_vhea_caret_offset = value(self.caret_offset);
+    }
+}
+
+/// The fontinfo field holding the default value of name ID `name_id`.
+/// Family and style names are left to the font and instance fields.
+fn name_field(info: &mut norad::FontInfo, name_id: u16) -> Option<&mut Option<String>> {
+    Some(match name_id {
+        0 => &mut info.copyright,
+        3 => &mut info.open_type_name_unique_id,
+        4 => &mut info.open_type_name_compatible_full_name,
+        5 => &mut info.open_type_name_version,
+        6 => &mut info.postscript_font_name,
+        7 => &mut info.trademark,
+        8 => &mut info.open_type_name_manufacturer,
+        9 => &mut info.open_type_name_designer,
+        10 => &mut info.open_type_name_description,
+        11 => &mut info.open_type_name_manufacturer_url,
+        12 => &mut info.open_type_name_designer_url,
+        13 => &mut info.open_type_name_license,
+        14 => &mut info.open_type_name_license_url,
+        16 => &mut info.open_type_name_preferred_family_name,
+        17 => &mut info.open_type_name_preferred_subfamily_name,
+        19 => &mut info.open_type_name_sample_text,
+        21 => &mut info.open_type_name_wws_family_name,
+        22 => &mut info.open_type_name_wws_subfamily_name,
+        _ => return None,
+    })
+}
+
+/// Whether a name record holds a localized value that font properties can
+/// express.
+fn is_localized_name(record: &norad::fontinfo::NameRecord) -> bool {
+    record.platform_id == u32::from(WINDOWS_PLATFORM_ID)
+        && record.encoding_id == u32::from(WINDOWS_UNICODE_BMP_ID)
+        && matches!(
+            u16::try_from(record.name_id).map(property_key),
+            Ok(Some((_, true)))
+        )
+        && u16::try_from(record.language_id)
+            .ok()
+            .and_then(Language::from_windows_id)
+            .is_some()
+}
+
+impl Font {
+    /// Write the font properties into `info`: default values to their
+    /// fontinfo fields, localized values as Windows name records. Name
+    /// records for other platforms, name IDs or languages are kept.
+    ///
+    /// Glyphs 2 fonts are read with [`Font::name_properties`]; those without
+    /// names leave `info` as it is.
+    pub fn apply_properties_to_font_info(&self, info: &mut norad::FontInfo) {
+        let Some(properties) = self.name_properties() else {
+            return;
+        };
+        let mut records: Vec<norad::fontinfo::NameRecord> = info
+            .open_type_name_records
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|record| !is_localized_name(record))
+            .collect();
+        for property in &properties {
+            let Some(name_id) = name_id(&property.key) else {
+                continue;
+            };
+            if let Some(field) = name_field(info, name_id) {
+                *field = property.default_value().map(str::to_string);
+            }
+            for localized in property.localized_values() {
+                let Some(language) = Language::from_tag(&localized.language) else {
+                    warn!(
+                        "Dropping {} value in unknown language {}",
+                        property.key, localized.language
+                    );
+                    continue;
+                };
+                records.push(norad::fontinfo::NameRecord {
+                    name_id: name_id.into(),
+                    platform_id: WINDOWS_PLATFORM_ID.into(),
+                    encoding_id: WINDOWS_UNICODE_BMP_ID.into(),
+                    language_id: language.windows_id.into(),
+                    string: localized.value.clone(),
+                });
+            }
+        }
+        info.open_type_name_records = (!records.is_empty()).then_some(records);
+    }
+}
+
+impl Property {
+    /// The font properties `info` holds, the inverse of
+    /// [`Font::apply_properties_to_font_info`].
+    pub fn from_font_info(info: &norad::FontInfo) -> Vec<Property> {
+        // Reading through a copy saves a read-only twin of `name_field`.
+        let mut fields = info.clone();
+        let mut properties: Vec<Property> = Vec::new();
+        for &(key, name_id, localizable) in NAME_PROPERTIES {
+            let default = name_field(&mut fields, name_id).and_then(Option::take);
+            let mut values: Vec<LocalizedValue> = default
+                .iter()
+                .map(|value| LocalizedValue {
+                    language: DEFAULT_LANGUAGE.into(),
+                    value: value.clone(),
+                })
+                .collect();
+            if localizable {
+                values.extend(
+                    info.open_type_name_records
+                        .iter()
+                        .flatten()
+                        .filter(|record| {
+                            is_localized_name(record) && record.name_id == name_id.into()
+                        })
+                        .filter_map(|record| {
+                            let language = Language::from_windows_id(record.language_id as u16)?;
+                            Some(LocalizedValue {
+                                language: language.tag.into(),
+                                value: record.string.clone(),
+                            })
+                        }),
+                );
+            }
+            if values.is_empty() {
+                continue;
+            }
+            properties.push(if localizable {
+                Property {
+                    key: key.into(),
+                    values: Some(values),
+                    ..Default::default()
+                }
+            } else {
+                Property {
+                    key: key.into(),
+                    value: default,
+                    ..Default::default()
+                }
+            });
+        }
+        properties
+    }
+}
+
+impl From<Plist> for plist::Value {
+    fn from(plist: Plist) -> Self {
+        match plist {
+            Plist::Dictionary(dict) => plist::Value::Dictionary(
+                dict.into_iter()
+                    .map(|(key, value)| (key, plist::Value::from(value)))
+                    .collect(),
+            ),
+            Plist::Array(array) => plist::Value::Array(array.into_iter().map(Into::into).collect()),
+            Plist::String(s) => plist::Value::String(s),
+            Plist::Integer(i) => plist::Value::Integer(i.into()),
+            Plist::Float(f) => plist::Value::Real(f),
+        }
+    }
+}
+
+impl TryFrom<&plist::Value> for Plist {
+    type Error = String;
+
+    /// Booleans become integers, like Glyphs.app stores them. Dates, data
+    /// and UIDs have no equivalent.
+    fn try_from(value: &plist::Value) -> Result<Self, Self::Error> {
+        Ok(match value {
+            plist::Value::Dictionary(dict) => Plist::Dictionary(
+                dict.iter()
+                    .map(|(key, value)| Ok((key.clone(), value.try_into()?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            plist::Value::Array(array) => Plist::Array(
+                array
+                    .iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_, _>>()?,
+            ),
+            plist::Value::String(s) => Plist::String(s.clone()),
+            plist::Value::Boolean(b) => Plist::Integer(*b as i64),
+            plist::Value::Integer(i) => match i.as_signed() {
+                Some(i) => Plist::Integer(i),
+                None => Plist::Float(i.as_unsigned().unwrap() as f64),
+            },
+            plist::Value::Real(f) => Plist::Float(*f),
+            _ => return Err(format!("cannot convert plist value {:?}", value)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small xorshift generator, so the round-trip properties are checked
+    /// over the same pseudo-random contours on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn point(&mut self, typ: norad::PointType, smooth: bool, id: usize) -> norad::ContourPoint {
+            let name = (self.below(4) == 0).then(|| norad::Name::new("name").unwrap());
+            let identifier =
+                (self.below(2) == 0).then(|| norad::Identifier::new(&format!("id{}", id)).unwrap());
+            norad::ContourPoint::new(
+                self.below(2000) as f64 - 1000.0,
+                self.below(2000) as f64 / 2.0,
+                typ,
+                smooth,
+                name,
+                identifier,
+                None,
+            )
+        }
+    }
+
+    /// A valid UFO contour: open, closed (possibly starting off-curve), or
+    /// a quadratic contour without on-curve points.
+    fn random_contour(rng: &mut Rng) -> norad::Contour {
+        use norad::PointType::*;
+
+        let mut points = Vec::new();
+        let mut id = 0;
+        let mut push = |rng: &mut Rng, typ, smooth| {
+            id += 1;
+            points.push(rng.point(typ, smooth, id));
+        };
+        let kind = rng.below(3);
+        if kind == 2 {
+            for _ in 0..rng.below(5) + 1 {
+                push(rng, OffCurve, false);
+            }
+        } else {
+            if kind == 0 {
+                push(rng, Move, false);
+            }
+            for _ in 0..rng.below(5) + 1 {
+                let (typ, off_curves) = match rng.below(3) {
+                    0 => (Line, 0),
+                    1 => (Curve, 2),
+                    _ => (QCurve, rng.below(3)),
+                };
+                for _ in 0..off_curves {
+                    push(rng, OffCurve, false);
+                }
+                let smooth = rng.below(2) == 0;
+                push(rng, typ, smooth);
+            }
+        }
+        if kind == 1 {
+            let start = rng.below(points.len() as u64) as usize;
+            points.rotate_left(start);
+        }
+        norad::Contour::new(points, None, None)
+    }
+
+    fn assert_same_contour(a: &norad::Contour, b: &norad::Contour) {
+        assert_eq!(a.points.len(), b.points.len(), "{:?} vs. {:?}", a, b);
+        for (p, q) in a.points.iter().zip(&b.points) {
+            assert_eq!(
+                (p.x, p.y, &p.typ, p.smooth, &p.name, p.identifier()),
+                (q.x, q.y, &q.typ, q.smooth, &q.name, q.identifier()),
+                "{:?} vs. {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn localized_names_roundtrip() {
+        let record =
+            |name_id, platform_id, language_id, string: &str| norad::fontinfo::NameRecord {
+                name_id,
+                platform_id,
+                encoding_id: if platform_id == 3 { 1 } else { 0 },
+                language_id,
+                string: string.into(),
+            };
+        let info = norad::FontInfo {
+            open_type_name_designer: Some("Designer".into()),
+            open_type_name_designer_url: Some("https://example.com".into()),
+            open_type_name_records: Some(vec![
+                record(9, 3, 0x0407, "Gestalter"),
+                record(1, 3, 0x0411, "テスト"),
+                record(1, 1, 0, "Mac name"),
+            ]),
+            ..Default::default()
+        };
+        let properties = Property::from_font_info(&info);
+        let keys: Vec<_> = properties.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["familyNames", "designers", "designerURL"]);
+        assert_eq!(properties[1].get("DEU"), Some("Gestalter"));
+        assert_eq!(properties[2].value.as_deref(), Some("https://example.com"));
+
+        let mut font = Font::load(&"../testdata/NewFontG3.glyphs").unwrap();
+        font.properties = Some(properties);
+        let mut roundtripped = norad::FontInfo {
+            open_type_name_records: Some(vec![record(1, 1, 0, "Mac name")]),
+            ..Default::default()
+        };
+        font.apply_properties_to_font_info(&mut roundtripped);
+        assert_eq!(
+            roundtripped.open_type_name_designer,
+            info.open_type_name_designer
+        );
+        let mut records = roundtripped.open_type_name_records.unwrap();
+        let mut expected = info.open_type_name_records.unwrap();
+        records.sort_by_key(|r| (r.name_id, r.platform_id));
+        expected.sort_by_key(|r| (r.name_id, r.platform_id));
+        assert_eq!(records, expected);
+    }
+
+    #[test]
+    fn contour_roundtrip() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let contour = random_contour(&mut rng);
+            let path: Path = (&contour).into();
+            assert_eq!(path.closed, contour.is_closed());
+            let roundtripped: norad::Contour = (&path).into();
+            assert_same_contour(&contour, &roundtripped);
+        }
+    }
+
+    #[test]
+    fn arbitrary_paths_convert_to_valid_contours() {
+        let types = [
+            NodeType::Line,
+            NodeType::LineSmooth,
+            NodeType::OffCurve,
+            NodeType::Curve,
+            NodeType::CurveSmooth,
+            NodeType::QCurve,
+            NodeType::QCurveSmooth,
+        ];
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let mut path = Path::new(rng.below(2) == 0);
+            for _ in 0..rng.below(6) {
+                let node_type = types[rng.below(types.len() as u64) as usize];
+                path.add((rng.below(100) as f64, 0.0), node_type);
+            }
+
+            let contour: norad::Contour = (&path).into();
+            let moves = contour
+                .points
+                .iter()
+                .filter(|p| p.typ == norad::PointType::Move)
+                .count();
+            if path.closed {
+                assert_eq!(contour.points.len(), path.nodes.len());
+                assert_eq!(moves, 0);
+            } else if let (Some(first), Some(last)) =
+                (contour.points.first(), contour.points.last())
+            {
+                assert_eq!(first.typ, norad::PointType::Move);
+                assert_eq!(moves, 1);
+                assert_ne!(last.typ, norad::PointType::OffCurve);
+            }
+        }
+    }
+}
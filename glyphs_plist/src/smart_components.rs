@@ -0,0 +1,204 @@
+//! Instantiating smart components at their piece values.
+
+use std::borrow::Cow;
+
+use crate::font::{Component, Font, Glyph, Layer};
+use crate::interpolation::VariationModel;
+
+impl Glyph {
+    /// Whether the glyph is a smart glyph, i.e. has smart axes.
+    pub fn is_smart(&self) -> bool {
+        self.parts_settings.iter().flatten().next().is_some()
+    }
+}
+
+impl Font {
+    /// Interpolate the smart glyph referenced by `component` at the
+    /// component's piece values, for the master of `layer`.
+    ///
+    /// The smart layers of the master are placed on the smart axes by their
+    /// part selection (a missing axis counts as the bottom pole), and piece
+    /// values outside an axis range are clamped to it.
+    pub fn instantiate_smart_component(
+        &self,
+        component: &Component,
+        layer: &Layer,
+    ) -> Result<Layer, String> {
+        let glyph = self
+            .get_glyph(&component.name)
+            .ok_or_else(|| format!("cannot find glyph '{}'", component.name))?;
+        let axes = glyph
+            .parts_settings
+            .as_deref()
+            .filter(|axes| !axes.is_empty())
+            .ok_or_else(|| format!("glyph '{}' is not a smart glyph", glyph.glyphname))?;
+
+        let master_id = layer.master_id();
+        let smart_layers: Vec<&Layer> = glyph
+            .layers
+            .iter()
+            .filter(|l| {
+                l.master_id() == master_id
+                    && (l.layer_id == master_id || l.part_selection.is_some())
+            })
+            .collect();
+
+        let locations: Vec<Vec<f64>> = smart_layers
+            .iter()
+            .map(|l| {
+                axes.iter()
+                    .map(|axis| {
+                        let pole = l.part_selection.as_ref().and_then(|p| p.get(&axis.name));
+                        match pole {
+                            Some(2) => 1.0,
+                            _ => 0.0,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let location: Vec<f64> = axes
+            .iter()
+            .map(|axis| {
+                let value = component
+                    .piece
+                    .as_ref()
+                    .and_then(|piece| piece.get(&axis.name))
+                    .copied()
+                    .unwrap_or(axis.bottom_value);
+                if axis.top_value == axis.bottom_value {
+                    0.0
+                } else {
+                    ((value - axis.bottom_value) / (axis.top_value - axis.bottom_value))
+                        .clamp(0.0, 1.0)
+                }
+            })
+            .collect();
+
+        let model = VariationModel::new(&locations)
+            .map_err(|e| format!("smart glyph '{}': {}", glyph.glyphname, e))?;
+        Layer::interpolate(&smart_layers, &model.weights(&location))
+            .map_err(|e| format!("smart glyph '{}': {}", glyph.glyphname, e))
+    }
+
+    /// Return a copy of `layer` with every smart component replaced by the
+    /// outline of its instance, or `None` if the layer has no smart
+    /// components. Components inside the instance are kept as components.
+    pub fn decompose_smart_components(&self, layer: &Layer) -> Result<Option<Layer>, String> {
+        let Some(components) = &layer.components else {
+            return Ok(None);
+        };
+        let is_smart = |c: &Component| matches!(self.get_glyph(&c.name), Some(g) if g.is_smart());
+        if !components.iter().any(is_smart) {
+            return Ok(None);
+        }
+
+        let mut decomposed = layer.clone();
+        let mut paths = layer.paths.clone().unwrap_or_default();
+        let mut new_components = Vec::new();
+        for component in components {
+            if !is_smart(component) {
+                new_components.push(component.clone());
+                continue;
+            }
+            let instance = self.instantiate_smart_component(component, layer)?;
+            let transform = component.transform.unwrap_or_default();
+            for path in instance.paths.iter().flatten() {
+                let mut path = path.clone();
+                path.apply_affine(transform);
+                paths.push(path);
+            }
+            for nested in instance.components.iter().flatten() {
+                let mut nested = nested.clone();
+                nested.transform = Some(transform * nested.transform.unwrap_or_default());
+                new_components.push(nested);
+            }
+        }
+        decomposed.paths = (!paths.is_empty()).then_some(paths);
+        decomposed.components = (!new_components.is_empty()).then_some(new_components);
+        Ok(Some(decomposed))
+    }
+
+    /// The layer a component draws for `origin`: the instance for smart
+    /// components, the corresponding base glyph layer otherwise. Smart
+    /// components that cannot be instantiated fall back to the latter.
+    pub(crate) fn component_layer(
+        &self,
+        component: &Component,
+        origin: &Layer,
+    ) -> Option<Cow<'_, Layer>> {
+        let base_glyph = self.get_glyph(&component.name)?;
+        if base_glyph.is_smart() {
+            if let Ok(instance) = self.instantiate_smart_component(component, origin) {
+                return Some(Cow::Owned(instance));
+            }
+        }
+        self.resolve_component(component, origin).map(Cow::Borrowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use kurbo::Affine;
+
+    use super::*;
+    use crate::fixtures::{font, glyph, path};
+    use crate::font::{PartProperty, Path};
+
+    fn bar(width: f64) -> Path {
+        path(
+            true,
+            &[(width, 0.0), (width, 100.0), (0.0, 100.0), (0.0, 0.0)],
+        )
+    }
+
+    #[test]
+    fn instantiate_and_decompose() {
+        let smart_layer = |layer_id: &str, width: f64, pole: Option<i64>| Layer {
+            layer_id: layer_id.into(),
+            associated_master_id: pole.map(|_| "m01".to_string()),
+            width,
+            paths: Some(vec![bar(width)]),
+            part_selection: pole.map(|pole| HashMap::from([("Width".to_string(), pole)])),
+            ..Default::default()
+        };
+        let smart = Glyph {
+            parts_settings: Some(vec![PartProperty {
+                name: "Width".into(),
+                bottom_value: 0.0,
+                top_value: 100.0,
+            }]),
+            ..glyph(
+                "_part.bar",
+                vec![
+                    smart_layer("m01", 100.0, None),
+                    smart_layer("wide", 500.0, Some(2)),
+                ],
+            )
+        };
+        let font = font(vec![smart]);
+
+        let layer = Layer {
+            layer_id: "m01".into(),
+            components: Some(vec![Component {
+                name: "_part.bar".into(),
+                transform: Some(Affine::translate((10.0, 0.0))),
+                piece: Some(HashMap::from([("Width".to_string(), 25.0)])),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let instance = font
+            .instantiate_smart_component(&layer.components.as_ref().unwrap()[0], &layer)
+            .unwrap();
+        assert_eq!(instance.width, 200.0);
+
+        let decomposed = font.decompose_smart_components(&layer).unwrap().unwrap();
+        assert!(decomposed.components.is_none());
+        let paths = decomposed.paths.unwrap();
+        assert_eq!(paths[0].nodes[0].pt, (210.0, 0.0).into());
+    }
+}
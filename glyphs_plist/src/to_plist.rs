@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 pub use glyphs_plist_derive::ToPlist;
 
 use crate::plist::Plist;
@@ -50,6 +52,15 @@ impl<T: ToPlist> ToPlist for Vec<T> {
     }
 }
 
+impl<T: ToPlist> ToPlist for HashMap<String, T> {
+    fn to_plist(self) -> Plist {
+        self.into_iter()
+            .map(|(key, value)| (key, ToPlist::to_plist(value)))
+            .collect::<HashMap<_, _>>()
+            .into()
+    }
+}
+
 impl<T: ToPlist> ToPlistOpt for T {
     fn to_plist(self) -> Option<Plist> {
         Some(ToPlist::to_plist(self))
@@ -14,11 +14,12 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
     let expanded = quote! {
         impl crate::from_plist::FromPlist for #name {
-            fn from_plist(plist: crate::plist::Plist) -> Self {
-                let mut hashmap = plist.into_hashmap();
-                #name {
+            fn from_plist(plist: crate::plist::Plist) -> Result<Self, String> {
+                let mut hashmap: HashMap<String, crate::plist::Plist> =
+                    crate::from_plist::FromPlist::from_plist(plist)?;
+                Ok(#name {
                     #deser
-                }
+                })
             }
         }
     };
@@ -53,11 +54,14 @@ fn add_deser(data: &Data) -> TokenStream {
                     if !is_rest(&f.attrs) {
                         let name = &f.ident;
                         let name_str = name.as_ref().unwrap().to_string();
-                        let snake_name = snake_to_camel_case(&name_str);
+                        // Raw identifiers like `r#type` map to the bare key.
+                        let name_str = name_str.trim_start_matches("r#");
+                        let snake_name = snake_to_camel_case(name_str);
                         Some(quote_spanned! {f.span() =>
                             #name: crate::from_plist::FromPlistOpt::from_plist(
                                 hashmap.remove(#snake_name)
-                            ),
+                            )
+                            .map_err(|e| format!("{}: {}", #snake_name, e))?,
                         })
                     } else {
                         None
@@ -92,7 +96,9 @@ fn add_ser(data: &Data) -> TokenStream {
                     if !is_rest(&f.attrs) {
                         let name = &f.ident;
                         let name_str = name.as_ref().unwrap().to_string();
-                        let snake_name = snake_to_camel_case(&name_str);
+                        // Raw identifiers like `r#type` map to the bare key.
+                        let name_str = name_str.trim_start_matches("r#");
+                        let snake_name = snake_to_camel_case(name_str);
                         Some(quote_spanned! {f.span() =>
                             if let Some(plist) = crate::to_plist::ToPlistOpt::to_plist(self.#name) {
                                 hashmap.insert(#snake_name.to_string(), plist);